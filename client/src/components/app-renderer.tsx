@@ -1,7 +1,7 @@
 import type { AppTable } from "@/types";
 import React, {
   memo,
-  useMemo,
+  useEffect,
   useState,
   type ComponentType,
   type FunctionComponent,
@@ -11,42 +11,89 @@ import CloseIcon from "./icons/close";
 import FullscreenIcon from "./icons/fullscreen";
 import { adaptiveIs3DModeAtom } from "@/state/3d";
 import { useAtomValue, useSetAtom } from "jotai";
-import { hostAPI } from "@/libs/host-api";
+import { issueAppToken } from "@/libs/host-api";
+import { miniServer } from "@/libs/mini-server";
+import CONFIG from "@/config";
 import { promptState } from "@/state/app-ecosystem";
 import { Button } from "./ui/button";
 
 // Import all UI components from components/ui/*
 import * as ui from "./ui";
-import toast from "react-hot-toast";
 import { useNavigate } from "react-router-dom";
 
 interface AppRendererProps {
   app: AppTable;
   component?: ComponentType<{ app: AppTable; ui: typeof ui }>;
+  /** Render unsaved source that isn't installed, without access to the database */
+  preview?: boolean;
+}
+
+// App code never runs in the dashboard's context: without allow-same-origin the
+// frame gets an opaque origin, so it can't read the dashboard's token or DOM
+const SANDBOX = "allow-scripts allow-forms allow-modals allow-popups allow-downloads";
+
+type Launch = { src: string } | { srcDoc: string };
+
+/** Where the app's frame loads from: its run page with a fresh token, or a preview page */
+async function launchApp(app: AppTable, preview: boolean): Promise<Launch> {
+  if (preview) {
+    const response = await miniServer.POST("/api/sources/preview", {
+      body: { source_code: app.source_code, name: app.name },
+      parseAs: "text",
+    } as any);
+    if (!response.data) {
+      throw new Error(`HTTP error! Failed to preview ${app.name}`);
+    }
+    return { srcDoc: response.data as string };
+  }
+
+  // The token travels in the fragment, so it never reaches server logs or referrers
+  const { token } = await issueAppToken(app.id);
+  return {
+    src: `${CONFIG.API.BASE_URL}/apps/${encodeURIComponent(app.id)}/run#token=${encodeURIComponent(token)}`,
+  };
 }
 
 const AppRenderer: FunctionComponent<AppRendererProps> = ({
   app,
-  component,
+  component: Component,
+  preview = false,
 }) => {
   const is3D = useAtomValue(adaptiveIs3DModeAtom);
   const [fullscreen, setFullscreen] = useState(false);
-
-  // Compose context to pass to AppComponent
-  const context = { app, React, ui, toast, hostAPI };
-
-  const DynamicComponent = useMemo(() => {
-    if (app.source_code) {
-      // Convert source code string to a React component factory, then call it to get the component
-      const factory = new Function(app.source_code) as () => FunctionComponent<
-        typeof context
-      >;
-      return factory();
-    }
-    return component!;
-  }, [app, component]);
-
-  const content = <DynamicComponent {...context} />;
+  const [launch, setLaunch] = useState<Launch>();
+  const [launchError, setLaunchError] = useState<Error>();
+
+  useEffect(() => {
+    if (!app.source_code) return;
+    let cancelled = false;
+    setLaunch(undefined);
+    setLaunchError(undefined);
+    launchApp(app, preview).then(
+      (launched) => !cancelled && setLaunch(launched),
+      (error: Error) => !cancelled && setLaunchError(error),
+    );
+    return () => {
+      cancelled = true;
+    };
+  }, [app.id, app.source_code, preview]);
+
+  // Let the error boundary show why the app couldn't start
+  if (launchError) throw launchError;
+
+  const content = app.source_code ? (
+    launch && (
+      <iframe
+        key={"src" in launch ? launch.src : launch.srcDoc}
+        title={app.name}
+        className="w-full h-full border-0"
+        sandbox={SANDBOX}
+        {...launch}
+      />
+    )
+  ) : (
+    Component && <Component app={app} ui={ui} />
+  );
 
   // Always show buttons, but handle different layouts for fullscreen vs normal mode
   if (is3D) {
@@ -94,7 +141,11 @@ const AppRenderer: FunctionComponent<AppRendererProps> = ({
 };
 
 const MemoizedAppRenderer = memo(AppRenderer, (prev, next) => {
-  return prev.app.id === next.app.id;
+  return (
+    prev.app.id === next.app.id &&
+    prev.app.source_code === next.app.source_code &&
+    prev.preview === next.preview
+  );
 });
 
 class ErrorBoundary extends React.Component<
@@ -1,6 +1,6 @@
 import { enabled3DModeAtom, adaptiveIs3DModeAtom } from "@/state/3d";
 import { useAtomValue, useSetAtom } from "jotai";
-import { hostAPI } from "@/libs/host-api";
+import { hostAPI, ResetCancelledError } from "@/libs/host-api";
 import { Avatar, AvatarFallback } from "@/components/ui/avatar";
 import {
   DropdownMenu,
@@ -26,6 +26,7 @@ export default function Profile() {
     try {
       await hostAPI.db.reset();
     } catch (error) {
+      if (error instanceof ResetCancelledError) return;
       console.warn("Failed to reset server database:", error);
     }
 
@@ -14,6 +14,39 @@ interface DatabaseDocument {
   updated_at: string;
 }
 
+interface AppToken {
+  token: string;
+  app_id: string;
+  expires_at: string;
+}
+
+interface ResetToken {
+  token: string;
+  collections?: string[] | null;
+  document_count: number;
+  expires_at: string;
+}
+
+/**
+ * Asks the user whether a prepared reset may go ahead. Runs in the host UI,
+ * never in app code, so an app can't wipe data without the user agreeing.
+ */
+type ConfirmReset = (prepared: ResetToken) => Promise<boolean>;
+
+const confirmInWindow: ConfirmReset = async (prepared) =>
+  window.confirm(
+    `Delete ${prepared.document_count} document(s)` +
+      (prepared.collections ? ` in ${prepared.collections.join(", ")}` : "") +
+      "? A snapshot is kept so this can be undone.",
+  );
+
+/** Thrown by `db.reset` when the user declines the reset */
+export class ResetCancelledError extends Error {
+  constructor() {
+    super("Database reset was cancelled");
+  }
+}
+
 interface DatabaseResponse<T> {
   data: T;
   links?: {
@@ -28,11 +61,34 @@ interface DatabaseResponse<T> {
   };
 }
 
-class HostAPI {
-  constructor() {
-    // Constructor no longer needs baseUrl since miniServer handles this
+/**
+ * Issue a token for one launch of an installed app. Only the dashboard can;
+ * the token is passed into the app's sandboxed frame, which sends it as
+ * X-App-Token so the server holds the app to the capabilities the user granted.
+ */
+export async function issueAppToken(appId: string): Promise<AppToken> {
+  const response = await miniServer.POST("/api/apps/{app_id}/tokens", {
+    params: { path: { app_id: appId } },
+  } as any);
+
+  if (!response.data) {
+    throw new Error(`HTTP error! Failed to get a token for app ${appId}`);
   }
 
+  // Type assertion needed since schema returns 'unknown'
+  return (response.data as DatabaseResponse<AppToken>).data;
+}
+
+/**
+ * The dashboard's own access to the database. Apps never get this: they run
+ * in sandboxed frames with their own host API.
+ */
+class HostAPI {
+  /**
+   * @param confirmReset Host UI that asks the user before a reset is carried out
+   */
+  constructor(private readonly confirmReset: ConfirmReset = confirmInWindow) {}
+
   /**
    * Database operations
    */
@@ -151,7 +207,7 @@ class HostAPI {
      * List all collections
      */
     collections: async (): Promise<string[]> => {
-      const response = await miniServer.GET("/api/db", {} as any);
+      const response = await miniServer.GET("/api/db");
 
       if (!response.data) {
         throw new Error(`HTTP error! Failed to get collections`);
@@ -163,10 +219,28 @@ class HostAPI {
     },
 
     /**
-     * Reset the entire database (clear all data)
+     * Reset the database (or only the given collections) once the user
+     * confirms it in the host UI. The server snapshots the data first so the
+     * reset can be undone.
      */
-    reset: async (): Promise<string> => {
-      const response = await miniServer.POST("/api/db/reset", {} as any);
+    reset: async (collections?: string[]): Promise<string> => {
+      const prepared = await miniServer.POST("/api/db/reset/prepare", {
+        body: { collections },
+      } as any);
+
+      if (!prepared.data) {
+        throw new Error(`HTTP error! Failed to prepare database reset`);
+      }
+
+      // Type assertion needed since schema returns 'unknown'
+      const resetToken = (prepared.data as DatabaseResponse<ResetToken>).data;
+      if (!(await this.confirmReset(resetToken))) {
+        throw new ResetCancelledError();
+      }
+
+      const response = await miniServer.POST("/api/db/reset", {
+        body: { token: resetToken.token },
+      } as any);
 
       if (!response.data) {
         throw new Error(`HTTP error! Failed to reset database`);
@@ -179,9 +253,9 @@ class HostAPI {
   };
 }
 
-// Export a singleton instance
+// Export a singleton instance for the dashboard's own calls
 export const hostAPI = new HostAPI();
 export default hostAPI;
 
 // Export types for external use
-export type { DatabaseDocument, DatabaseResponse };
+export type { AppToken, ConfirmReset, DatabaseDocument, DatabaseResponse, ResetToken };
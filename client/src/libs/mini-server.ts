@@ -1,7 +1,49 @@
-import createClient from "openapi-fetch";
+import createClient, { type Middleware } from "openapi-fetch";
 import type { paths } from "./mini-server.schema";
 import CONFIG from "../config";
 
+const DASHBOARD_TOKEN_KEY = "dashboard-token";
+
+/** The server answers every /api route only for the dashboard or an app instance */
+const PROTECTED_PATH = /^\/api\//;
+
+/**
+ * The token the dashboard authenticates with: the server's DASHBOARD_TOKEN, or
+ * the one it logs at startup. Asked for once and kept in this origin's
+ * storage; apps run in sandboxed frames on another origin, so their code can't
+ * read it.
+ */
+export function dashboardToken(): string | null {
+  let token =
+    localStorage.getItem(DASHBOARD_TOKEN_KEY) ??
+    import.meta.env.VITE_DASHBOARD_TOKEN ??
+    null;
+  if (!token) {
+    token = window.prompt(
+      "Dashboard token (the server's DASHBOARD_TOKEN, or the one it logged at startup)",
+    );
+    if (token) localStorage.setItem(DASHBOARD_TOKEN_KEY, token);
+  }
+  return token;
+}
+
+const dashboardAuth: Middleware = {
+  onRequest({ request }) {
+    if (!PROTECTED_PATH.test(new URL(request.url).pathname)) return request;
+    const token = dashboardToken();
+    if (token) request.headers.set("Authorization", `Bearer ${token}`);
+    return request;
+  },
+  onResponse({ request, response }) {
+    // Forget a token the server refused, so the next request asks again
+    if (response.status === 401 && request.headers.has("Authorization")) {
+      localStorage.removeItem(DASHBOARD_TOKEN_KEY);
+    }
+    return response;
+  },
+};
+
 export const miniServer = createClient<paths>({
   baseUrl: CONFIG.API.BASE_URL,
 });
+miniServer.use(dashboardAuth);
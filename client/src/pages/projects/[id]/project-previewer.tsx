@@ -25,8 +25,9 @@ export default function ProjectPreview() {
           icon: project.icon,
           price: project.price,
           version: `${project.currentVersion}`,
-          installed: 1,
+          installed: false,
         }}
+        preview
       />
     </AppPreviewErrorBoundary>
   );
@@ -32,7 +32,7 @@ export interface AppTable {
   icon: string;
   price: number | undefined;
   version: string | undefined;
-  installed: number;
+  installed: boolean;
   source_code?: string | null;
 }
 
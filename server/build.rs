@@ -0,0 +1,52 @@
+//! Builds the registry of built-in project templates from `templates/`.
+//!
+//! Every `templates/<id>.js` is a template. It starts with `// key: value` comment lines
+//! giving its `name`, `description` and `icon`; the rest of the file is its source code.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const TEMPLATE_FIELDS: [&str; 3] = ["name", "description", "icon"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=templates");
+
+    let mut paths: Vec<_> = fs::read_dir("templates")
+        .expect("templates directory is readable")
+        .map(|entry| entry.expect("templates directory is readable").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "js"))
+        .collect();
+    paths.sort();
+
+    let mut registry = String::from("&[\n");
+    for path in &paths {
+        let id = path.file_stem().unwrap().to_string_lossy();
+        let file = fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+        let mut fields = Vec::new();
+        let mut source_code = file.as_str();
+        while let Some((line, rest)) = source_code.split_once('\n') {
+            let Some((key, value)) = line.strip_prefix("//").and_then(|line| line.split_once(':')) else {
+                break;
+            };
+            fields.push((key.trim().to_string(), value.trim().to_string()));
+            source_code = rest;
+        }
+
+        registry.push_str(&format!("    BuiltinTemplate {{\n        id: {:?},\n", id));
+        for field in TEMPLATE_FIELDS {
+            let value = fields
+                .iter()
+                .find(|(key, _)| key == field)
+                .map(|(_, value)| value)
+                .unwrap_or_else(|| panic!("{} has no `// {}:` header line", path.display(), field));
+            registry.push_str(&format!("        {}: {:?},\n", field, value));
+        }
+        registry.push_str(&format!("        source_code: {:?},\n    }},\n", source_code));
+    }
+    registry.push(']');
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("builtin_templates.rs"), registry).unwrap();
+}
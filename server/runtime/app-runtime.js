@@ -0,0 +1,537 @@
+// Standalone runtime for apps served outside the dashboard.
+//
+// Provides the same props the dashboard's AppRenderer passes to an app component
+// ({ app, React, ui, toast, hostAPI }) using plain React and CSS, then renders the app.
+(function () {
+  "use strict";
+
+  var React = window.React;
+  var ReactDOM = window.ReactDOM;
+  var h = React.createElement;
+  var config = JSON.parse(document.getElementById("app-data").textContent);
+
+  function cx() {
+    return Array.prototype.filter.call(arguments, Boolean).join(" ");
+  }
+
+  // Wrap an element in a component accepting className and the usual props
+  function el(tag, base, variants) {
+    var Component = React.forwardRef(function (props, ref) {
+      var rest = Object.assign({}, props);
+      var classes = [base];
+      Object.keys(variants || {}).forEach(function (name) {
+        var value = rest[name] || variants[name].default;
+        delete rest[name];
+        if (value && variants[name][value]) classes.push(variants[name][value]);
+      });
+      delete rest.asChild;
+      rest.className = cx.apply(null, classes.concat(props.className));
+      rest.ref = ref;
+      return h(tag, rest);
+    });
+    Component.displayName = base;
+    return Component;
+  }
+
+  // ---- ui kit ----
+
+  var TabsContext = React.createContext(null);
+
+  function Tabs(props) {
+    var controlled = props.value !== undefined;
+    var state = React.useState(props.defaultValue);
+    var value = controlled ? props.value : state[0];
+    var setValue = function (next) {
+      if (!controlled) state[1](next);
+      if (props.onValueChange) props.onValueChange(next);
+    };
+    return h(
+      TabsContext.Provider,
+      { value: { value: value, setValue: setValue } },
+      h("div", { className: cx("ui-tabs", props.className) }, props.children)
+    );
+  }
+
+  function TabsTrigger(props) {
+    var tabs = React.useContext(TabsContext);
+    var active = tabs && tabs.value === props.value;
+    return h(
+      "button",
+      {
+        type: "button",
+        className: cx("ui-tabs-trigger", active && "ui-active", props.className),
+        disabled: props.disabled,
+        onClick: function () {
+          if (tabs) tabs.setValue(props.value);
+        },
+      },
+      props.children
+    );
+  }
+
+  function TabsContent(props) {
+    var tabs = React.useContext(TabsContext);
+    if (!tabs || tabs.value !== props.value) return null;
+    return h("div", { className: cx("ui-tabs-content", props.className) }, props.children);
+  }
+
+  function Checkbox(props) {
+    return h("input", {
+      type: "checkbox",
+      id: props.id,
+      className: cx("ui-checkbox", props.className),
+      checked: props.checked === undefined ? undefined : !!props.checked,
+      defaultChecked: props.defaultChecked,
+      disabled: props.disabled,
+      onChange: function (event) {
+        if (props.onCheckedChange) props.onCheckedChange(event.target.checked);
+        if (props.onChange) props.onChange(event);
+      },
+    });
+  }
+
+  // Popups (dropdowns, sheets, popovers, tooltips) share one open/close model
+  function popup(kind) {
+    var Context = React.createContext(null);
+
+    function Root(props) {
+      var state = React.useState(!!props.defaultOpen);
+      var open = props.open !== undefined ? props.open : state[0];
+      var setOpen = function (next) {
+        state[1](next);
+        if (props.onOpenChange) props.onOpenChange(next);
+      };
+      return h(
+        Context.Provider,
+        { value: { open: open, setOpen: setOpen } },
+        h("div", { className: "ui-" + kind }, props.children)
+      );
+    }
+
+    function Trigger(props) {
+      var popupState = React.useContext(Context);
+      var toggle = function () {
+        popupState.setOpen(!popupState.open);
+      };
+      if (props.asChild && React.isValidElement(props.children)) {
+        return React.cloneElement(props.children, { onClick: toggle });
+      }
+      return h("button", { type: "button", className: props.className, onClick: toggle }, props.children);
+    }
+
+    function Content(props) {
+      var popupState = React.useContext(Context);
+      if (!popupState || !popupState.open) return null;
+      return h(
+        "div",
+        {
+          className: cx("ui-" + kind + "-content", props.className),
+          onClick: kind === "dropdown" ? function () { popupState.setOpen(false); } : undefined,
+        },
+        props.children
+      );
+    }
+
+    function Close(props) {
+      var popupState = React.useContext(Context);
+      return h(
+        "button",
+        { type: "button", className: props.className, onClick: function () { popupState.setOpen(false); } },
+        props.children
+      );
+    }
+
+    return { Root: Root, Trigger: Trigger, Content: Content, Close: Close };
+  }
+
+  var dropdown = popup("dropdown");
+  var sheet = popup("sheet");
+  var popover = popup("popover");
+  var collapsible = popup("collapsible");
+
+  function Tooltip(props) {
+    return h("span", { className: "ui-tooltip" }, props.children);
+  }
+
+  function Passthrough(props) {
+    return h(React.Fragment, null, props.children);
+  }
+
+  function Avatar(props) {
+    return h("span", { className: cx("ui-avatar", props.className) }, props.children);
+  }
+
+  function AvatarImage(props) {
+    var failed = React.useState(false);
+    if (!props.src || failed[0]) return null;
+    return h("img", {
+      src: props.src,
+      alt: props.alt,
+      className: "ui-avatar-image",
+      onError: function () { failed[1](true); },
+    });
+  }
+
+  var components = {
+    Container: el("div", "ui-container"),
+    Button: el("button", "ui-button", {
+      variant: {
+        default: "ui-button-default",
+        destructive: "ui-button-destructive",
+        outline: "ui-button-outline",
+        secondary: "ui-button-secondary",
+        ghost: "ui-button-ghost",
+        link: "ui-button-link",
+      },
+      size: { default: "ui-size-default", sm: "ui-size-sm", lg: "ui-size-lg", icon: "ui-size-icon" },
+    }),
+    Input: el("input", "ui-input"),
+    Textarea: el("textarea", "ui-textarea"),
+    Label: el("label", "ui-label"),
+    Card: el("div", "ui-card"),
+    CardHeader: el("div", "ui-card-header"),
+    CardTitle: el("div", "ui-card-title"),
+    CardDescription: el("div", "ui-card-description"),
+    CardAction: el("div", "ui-card-action"),
+    CardContent: el("div", "ui-card-content"),
+    CardFooter: el("div", "ui-card-footer"),
+    Badge: el("span", "ui-badge", {
+      variant: {
+        default: "ui-badge-default",
+        secondary: "ui-badge-secondary",
+        destructive: "ui-badge-destructive",
+        outline: "ui-badge-outline",
+      },
+    }),
+    Separator: el("hr", "ui-separator"),
+    Skeleton: el("div", "ui-skeleton"),
+    ScrollArea: el("div", "ui-scroll-area"),
+    ScrollBar: Passthrough,
+    Avatar: Avatar,
+    AvatarImage: AvatarImage,
+    AvatarFallback: el("span", "ui-avatar-fallback"),
+    Checkbox: Checkbox,
+    Tabs: Tabs,
+    TabsList: el("div", "ui-tabs-list"),
+    TabsTrigger: TabsTrigger,
+    TabsContent: TabsContent,
+    DropdownMenu: dropdown.Root,
+    DropdownMenuTrigger: dropdown.Trigger,
+    DropdownMenuContent: dropdown.Content,
+    DropdownMenuItem: el("div", "ui-dropdown-item"),
+    DropdownMenuLabel: el("div", "ui-dropdown-label"),
+    DropdownMenuSeparator: el("hr", "ui-separator"),
+    DropdownMenuGroup: Passthrough,
+    Sheet: sheet.Root,
+    SheetTrigger: sheet.Trigger,
+    SheetContent: sheet.Content,
+    SheetClose: sheet.Close,
+    SheetHeader: el("div", "ui-card-header"),
+    SheetFooter: el("div", "ui-card-footer"),
+    SheetTitle: el("div", "ui-card-title"),
+    SheetDescription: el("div", "ui-card-description"),
+    Popover: popover.Root,
+    PopoverTrigger: popover.Trigger,
+    PopoverContent: popover.Content,
+    Collapsible: collapsible.Root,
+    CollapsibleTrigger: collapsible.Trigger,
+    CollapsibleContent: collapsible.Content,
+    Tooltip: Tooltip,
+    TooltipTrigger: Passthrough,
+    TooltipContent: function () { return null; },
+    TooltipProvider: Passthrough,
+  };
+
+  // Anything the dashboard kit has that this runtime doesn't still renders its children
+  var ui = typeof Proxy === "function"
+    ? new Proxy(components, {
+        get: function (target, name) {
+          if (name in target) return target[name];
+          if (typeof name !== "string" || !/^[A-Z]/.test(name)) return undefined;
+          target[name] = el("div", "ui-" + name.toLowerCase());
+          return target[name];
+        },
+      })
+    : components;
+
+  // ---- toast ----
+
+  var toaster = document.createElement("div");
+  toaster.className = "ui-toaster";
+  document.body.appendChild(toaster);
+  var toastCount = 0;
+
+  function show(message, kind, options) {
+    options = options || {};
+    var id = options.id || "toast-" + ++toastCount;
+    var existing = document.getElementById(id);
+    var node = existing || document.createElement("div");
+    node.id = id;
+    node.className = "ui-toast ui-toast-" + kind;
+    node.textContent = (options.icon ? options.icon + " " : "") + (typeof message === "string" ? message : String(message));
+    if (!existing) toaster.appendChild(node);
+    if (kind !== "loading") {
+      setTimeout(function () { dismiss(id); }, options.duration || (kind === "error" ? 4000 : 2000));
+    }
+    return id;
+  }
+
+  function dismiss(id) {
+    if (id === undefined) {
+      toaster.innerHTML = "";
+      return;
+    }
+    var node = document.getElementById(id);
+    if (node) node.remove();
+  }
+
+  var toast = function (message, options) { return show(message, "blank", options); };
+  toast.success = function (message, options) { return show(message, "success", options); };
+  toast.error = function (message, options) { return show(message, "error", options); };
+  toast.loading = function (message, options) { return show(message, "loading", options); };
+  toast.custom = function (message, options) { return show(message, "blank", options); };
+  toast.dismiss = dismiss;
+  toast.dismissAll = function () { dismiss(); };
+  toast.remove = dismiss;
+  toast.promise = function (promise, messages, options) {
+    var id = toast.loading(messages.loading, options);
+    promise.then(
+      function (value) {
+        var message = typeof messages.success === "function" ? messages.success(value) : messages.success;
+        show(message, "success", Object.assign({}, options, { id: id }));
+      },
+      function (error) {
+        var message = typeof messages.error === "function" ? messages.error(error) : messages.error;
+        show(message, "error", Object.assign({}, options, { id: id }));
+      }
+    );
+    return promise;
+  };
+
+  // ---- hostAPI ----
+
+  // The dashboard launches each instance with a token of its own in the URL fragment, which
+  // never reaches the server; it is dropped from the address bar once read
+  var token = null;
+  var launch = /(?:^#|&)token=([^&]*)/.exec(window.location.hash);
+  if (launch) {
+    token = decodeURIComponent(launch[1]);
+    try {
+      history.replaceState(null, "", window.location.pathname + window.location.search);
+    } catch (e) {
+      // Sandboxed pages may not be allowed to; the token stays in the fragment
+    }
+  }
+
+  function send(method, path, body) {
+    // The token ties requests to this app, so the server can check them against its
+    // capabilities and keep its collections apart from other apps'
+    var headers = token ? { "X-App-Token": token } : {};
+    if (body) headers["Content-Type"] = "application/json";
+    return fetch(config.apiBase + path, {
+      method: method,
+      headers: headers,
+      body: body ? JSON.stringify(body) : undefined,
+    });
+  }
+
+  function request(method, path, body) {
+    return send(method, path, body)
+      .then(function (response) {
+        if (response.status === 401) {
+          // Only the dashboard can issue tokens; an expired one means launching the app again
+          var expired = new Error("This app's session has ended; open it again from the dashboard");
+          expired.status = 401;
+          throw expired;
+        }
+        if (!response.ok) {
+          var error = new Error("HTTP error! " + method + " " + path + " failed with " + response.status);
+          error.status = response.status;
+          throw error;
+        }
+        return response.status === 204 ? null : response.json();
+      });
+  }
+
+  function collectionPath(collection, id) {
+    return "/db/" + encodeURIComponent(collection) + (id === undefined ? "" : "/" + encodeURIComponent(id));
+  }
+
+  var serverDb = {
+    create: function (collection, data) {
+      return request("POST", collectionPath(collection), { data: data }).then(function (r) { return r.data; });
+    },
+    get: function (collection, id) {
+      return request("GET", collectionPath(collection, id)).then(
+        function (r) { return r.data; },
+        function (error) { if (error.status === 404) return null; throw error; }
+      );
+    },
+    update: function (collection, id, data) {
+      return request("PUT", collectionPath(collection, id), { data: data }).then(
+        function (r) { return r.data; },
+        function (error) { if (error.status === 404) return null; throw error; }
+      );
+    },
+    delete: function (collection, id) {
+      return request("DELETE", collectionPath(collection, id)).then(function () { return true; });
+    },
+    list: function (collection, limit, offset) {
+      var query = "?limit=" + (limit || 100) + "&offset=" + (offset || 0);
+      return request("GET", collectionPath(collection) + query).then(function (r) {
+        return { documents: r.data, count: (r.meta && r.meta.count) || 0 };
+      });
+    },
+    collections: function () {
+      return request("GET", "/db").then(function (r) { return r.data; });
+    },
+  };
+
+  // Exported apps run without a server, so their data lives in the browser's localStorage,
+  // one key per collection, in the same shape the database API returns
+  var localDb = (function () {
+    var prefix = "node-app:" + config.app.id + ":";
+    var memory = {};
+    var storage = null;
+    try {
+      storage = window.localStorage;
+      storage.getItem(prefix);
+    } catch (e) {
+      storage = null;
+    }
+
+    function load(collection) {
+      var raw = storage ? storage.getItem(prefix + collection) : memory[collection];
+      return raw ? JSON.parse(raw) : [];
+    }
+
+    function save(collection, documents) {
+      var raw = JSON.stringify(documents);
+      if (storage) storage.setItem(prefix + collection, raw);
+      else memory[collection] = raw;
+    }
+
+    function keys() {
+      if (!storage) return Object.keys(memory);
+      var names = [];
+      for (var i = 0; i < storage.length; i++) {
+        var key = storage.key(i);
+        if (key.indexOf(prefix) === 0) names.push(key.slice(prefix.length));
+      }
+      return names;
+    }
+
+    function newId() {
+      if (window.crypto && window.crypto.randomUUID) return window.crypto.randomUUID();
+      return Date.now().toString(36) + Math.random().toString(36).slice(2);
+    }
+
+    function settle(fn) {
+      return new Promise(function (resolve) { resolve(fn()); });
+    }
+
+    return {
+      create: function (collection, data) {
+        return settle(function () {
+          var now = new Date().toISOString();
+          var doc = { id: newId(), collection: collection, data: data, created_at: now, updated_at: now };
+          var documents = load(collection);
+          documents.unshift(doc);
+          save(collection, documents);
+          return doc;
+        });
+      },
+      get: function (collection, id) {
+        return settle(function () {
+          return load(collection).filter(function (doc) { return doc.id === id; })[0] || null;
+        });
+      },
+      update: function (collection, id, data) {
+        return settle(function () {
+          var documents = load(collection);
+          var doc = documents.filter(function (doc) { return doc.id === id; })[0];
+          if (!doc) return null;
+          doc.data = data;
+          doc.updated_at = new Date().toISOString();
+          save(collection, documents);
+          return doc;
+        });
+      },
+      delete: function (collection, id) {
+        return settle(function () {
+          var documents = load(collection);
+          var remaining = documents.filter(function (doc) { return doc.id !== id; });
+          if (remaining.length === documents.length) {
+            var error = new Error("Document " + id + " not found in " + collection);
+            error.status = 404;
+            throw error;
+          }
+          save(collection, remaining);
+          return true;
+        });
+      },
+      list: function (collection, limit, offset) {
+        return settle(function () {
+          var documents = load(collection);
+          var start = offset || 0;
+          return { documents: documents.slice(start, start + (limit || 100)), count: documents.length };
+        });
+      },
+      collections: function () {
+        return settle(function () {
+          return keys().filter(function (name) { return load(name).length > 0; }).sort();
+        });
+      },
+    };
+  })();
+
+  var hostAPI = { db: config.apiBase ? serverDb : localDb };
+
+  // ---- bootstrap ----
+
+  var ErrorBoundary = (function () {
+    function Boundary(props) {
+      React.Component.call(this, props);
+      this.state = { error: null };
+    }
+    Boundary.prototype = Object.create(React.Component.prototype);
+    Boundary.prototype.constructor = Boundary;
+    Boundary.getDerivedStateFromError = function (error) {
+      return { error: error };
+    };
+    Boundary.prototype.render = function () {
+      if (this.state.error) return renderError(this.state.error);
+      return this.props.children;
+    };
+    return Boundary;
+  })();
+
+  function renderError(error) {
+    return h(
+      "div",
+      { className: "ui-app-error" },
+      h("div", { className: "ui-app-error-icon" }, "🚧"),
+      h("h2", null, "App Execution Error"),
+      h("p", null, error && error.message ? error.message : String(error))
+    );
+  }
+
+  if (window.matchMedia && window.matchMedia("(prefers-color-scheme: dark)").matches) {
+    document.documentElement.classList.add("dark");
+  }
+
+  var root = ReactDOM.createRoot(document.getElementById("root"));
+  var context = { app: config.app, React: React, ui: ui, toast: toast, hostAPI: hostAPI };
+  try {
+    if (!config.app.source_code) {
+      throw new Error("This app has no source code yet");
+    }
+    var AppComponent = new Function(config.app.source_code)();
+    if (typeof AppComponent !== "function") {
+      throw new Error("The app's source code doesn't return a component");
+    }
+    root.render(h(ErrorBoundary, null, h(AppComponent, context)));
+  } catch (error) {
+    root.render(renderError(error));
+  }
+})();
@@ -0,0 +1,238 @@
+// Headless smoke test for app source, run by the server in an embedded JavaScript engine.
+//
+// Evaluates `__appSource` the way the app runtime does, checks it returns a function
+// component and renders it once against stub React, ui, toast and hostAPI objects. Effects
+// aren't run, as on a first render. Evaluates to a JSON string:
+// { "ok": true } or { "ok": false, "stage": "evaluate" | "component" | "render", "message": "..." }.
+(function () {
+  "use strict";
+
+  var ELEMENT = { element: true };
+  var MAX_DEPTH = 200;
+
+  // ---- browser globals apps commonly touch while rendering ----
+
+  function noop() {}
+  var nextTimer = 1;
+  function timer() { return nextTimer++; }
+  var memoryStorage = {
+    getItem: function () { return null; },
+    setItem: noop,
+    removeItem: noop,
+    clear: noop,
+    key: function () { return null; },
+    length: 0,
+  };
+  var globals = {
+    console: { log: noop, info: noop, warn: noop, error: noop, debug: noop },
+    setTimeout: timer,
+    setInterval: timer,
+    clearTimeout: noop,
+    clearInterval: noop,
+    requestAnimationFrame: timer,
+    cancelAnimationFrame: noop,
+    localStorage: memoryStorage,
+    sessionStorage: memoryStorage,
+    navigator: { userAgent: "smoke-test", language: "en-US", clipboard: { writeText: function () { return Promise.resolve(); } } },
+    matchMedia: function () { return { matches: false, addEventListener: noop, removeEventListener: noop, addListener: noop, removeListener: noop }; },
+    addEventListener: noop,
+    removeEventListener: noop,
+    innerWidth: 1024,
+    innerHeight: 768,
+  };
+  Object.keys(globals).forEach(function (name) {
+    if (typeof globalThis[name] === "undefined") globalThis[name] = globals[name];
+  });
+  if (typeof globalThis.window === "undefined") globalThis.window = globalThis;
+  if (typeof globalThis.Intl === "undefined") {
+    var formatter = function () { return { format: function (value) { return String(value); } }; };
+    globalThis.Intl = { NumberFormat: formatter, DateTimeFormat: formatter };
+  }
+
+  // ---- React ----
+
+  function createElement(type, props) {
+    if (type === undefined || type === null) {
+      throw new Error("Element type is invalid: expected a string or a component but got: " + type);
+    }
+    var elementProps = {};
+    if (props) for (var key in props) if (key !== "key" && key !== "ref") elementProps[key] = props[key];
+    if (arguments.length > 2) {
+      elementProps.children = arguments.length === 3 ? arguments[2] : Array.prototype.slice.call(arguments, 2);
+    }
+    return { $$typeof: ELEMENT, type: type, props: elementProps };
+  }
+
+  function isValidElement(value) {
+    return Boolean(value) && value.$$typeof === ELEMENT;
+  }
+
+  function toArray(children) {
+    var result = [];
+    (function flatten(child) {
+      if (Array.isArray(child)) child.forEach(flatten);
+      else if (child !== null && child !== undefined && typeof child !== "boolean") result.push(child);
+    })(children);
+    return result;
+  }
+
+  function createContext(defaultValue) {
+    var context = { _currentValue: defaultValue };
+    context.Provider = { context: context };
+    context.Consumer = { context: context };
+    return context;
+  }
+
+  var React = {
+    createElement: createElement,
+    cloneElement: function (element, props) {
+      var merged = Object.assign({}, element.props, props);
+      if (arguments.length > 2) merged.children = Array.prototype.slice.call(arguments, 2);
+      return { $$typeof: ELEMENT, type: element.type, props: merged };
+    },
+    isValidElement: isValidElement,
+    createContext: createContext,
+    forwardRef: function (render) { return function (props) { return render(props, null); }; },
+    memo: function (component) { return component; },
+    lazy: function () { return function () { return null; }; },
+    Fragment: "Fragment",
+    StrictMode: "StrictMode",
+    Suspense: "Suspense",
+    Children: {
+      toArray: toArray,
+      map: function (children, fn) { return toArray(children).map(fn); },
+      forEach: function (children, fn) { toArray(children).forEach(fn); },
+      count: function (children) { return toArray(children).length; },
+      only: function (children) { return toArray(children)[0]; },
+    },
+    useState: function (initial) {
+      return [typeof initial === "function" ? initial() : initial, noop];
+    },
+    useReducer: function (reducer, initialArg, init) {
+      return [init ? init(initialArg) : initialArg, noop];
+    },
+    useEffect: noop,
+    useLayoutEffect: noop,
+    useInsertionEffect: noop,
+    useImperativeHandle: noop,
+    useDebugValue: noop,
+    useRef: function (initial) { return { current: initial }; },
+    useMemo: function (create) { return create(); },
+    useCallback: function (callback) { return callback; },
+    useContext: function (context) { return context._currentValue; },
+    useId: function () { return ":r0:"; },
+    useTransition: function () { return [false, function (callback) { callback(); }]; },
+    useDeferredValue: function (value) { return value; },
+    useSyncExternalStore: function (subscribe, getSnapshot) { return getSnapshot(); },
+  };
+  React.Component = function Component(props) { this.props = props; this.state = {}; };
+  React.Component.prototype.isReactComponent = {};
+  React.Component.prototype.setState = noop;
+  React.Component.prototype.forceUpdate = noop;
+  React.PureComponent = React.Component;
+
+  // ---- ui kit, toast and hostAPI ----
+
+  // Any component name renders its children, like the app runtime's fallback
+  var components = {};
+  var ui = new Proxy({}, {
+    get: function (target, name) {
+      if (typeof name !== "string") return undefined;
+      if (!components[name]) {
+        components[name] = function (props) { return props.children === undefined ? null : props.children; };
+      }
+      return components[name];
+    },
+  });
+
+  var toast = function () { return "toast"; };
+  ["success", "error", "loading", "custom", "dismiss", "dismissAll", "remove"].forEach(function (name) {
+    toast[name] = function () { return "toast"; };
+  });
+  toast.promise = function (promise) { return promise; };
+
+  var now = new Date().toISOString();
+  var hostAPI = {
+    db: {
+      create: function (collection, data) {
+        return Promise.resolve({ id: "smoke-test", collection: collection, data: data, created_at: now, updated_at: now });
+      },
+      get: function () { return Promise.resolve(null); },
+      update: function (collection, id, data) {
+        return Promise.resolve({ id: id, collection: collection, data: data, created_at: now, updated_at: now });
+      },
+      delete: function () { return Promise.resolve(true); },
+      list: function () { return Promise.resolve({ documents: [], count: 0 }); },
+      collections: function () { return Promise.resolve([]); },
+      reset: function () { return Promise.resolve("Database reset successfully"); },
+    },
+  };
+
+  // ---- rendering ----
+
+  var path = [];
+
+  function componentName(type) {
+    return type.displayName || type.name || "Anonymous";
+  }
+
+  function render(node, depth) {
+    if (depth > MAX_DEPTH) throw new Error("Components are nested more than " + MAX_DEPTH + " deep");
+    if (node === null || node === undefined || typeof node === "boolean") return;
+    if (typeof node === "string" || typeof node === "number") return;
+    if (Array.isArray(node)) {
+      node.forEach(function (child) { render(child, depth + 1); });
+      return;
+    }
+    if (!isValidElement(node)) {
+      if (typeof node === "function") throw new Error("Functions are not valid as a React child");
+      throw new Error("Objects are not valid as a React child (found: object with keys {" + Object.keys(node).join(", ") + "})");
+    }
+
+    var type = node.type;
+    if (typeof type === "function") {
+      path.push(componentName(type));
+      var output = type.prototype && type.prototype.isReactComponent
+        ? new type(node.props).render()
+        : type(node.props);
+      render(output, depth + 1);
+      path.pop();
+      return;
+    }
+    if (type && type.context && type === type.context.Consumer) {
+      render(node.props.children(type.context._currentValue), depth + 1);
+      return;
+    }
+    render(node.props.children, depth + 1);
+  }
+
+  function failure(stage, error) {
+    var message = error && error.message !== undefined ? String(error.message) : String(error);
+    if (stage === "render" && path.length) message += " (in " + path.join(" > ") + ")";
+    return JSON.stringify({ ok: false, stage: stage, message: message });
+  }
+
+  var Component;
+  try {
+    Component = new Function(globalThis.__appSource)();
+  } catch (error) {
+    return failure("evaluate", error);
+  }
+
+  if (typeof Component !== "function") {
+    return JSON.stringify({
+      ok: false,
+      stage: "component",
+      message: "The source returned " + (Component === null ? "null" : typeof Component) + " instead of a function component",
+    });
+  }
+
+  var app = { id: "smoke-test", name: "Smoke test", description: "", icon: "📱", version: "0.0.0" };
+  try {
+    render(createElement(Component, { app: app, React: React, ui: ui, toast: toast, hostAPI: hostAPI }), 0);
+  } catch (error) {
+    return failure("render", error);
+  }
+
+  return JSON.stringify({ ok: true });
+})();
@@ -32,7 +32,7 @@ pub async fn anthropic_request(
     body: Option<&serde_json::Value>,
     response_handling: ResponseHandling,
 ) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
-    let api_key = Config::anthropic_api_key().map_err(|e| e)?;
+    let api_key = Config::anthropic_api_key()?;
 
     let mut request_builder = match method {
         RequestMethod::Get => client.get(endpoint_url),
@@ -163,6 +163,7 @@ pub struct StreamingMessage {
     pub message_type: Option<String>,
     pub role: Option<String>,
     pub content: Option<Vec<StreamingContent>>,
+    pub usage: Option<UsageInfo>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -179,9 +180,11 @@ pub struct StreamingDelta {
     pub text: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct UsageInfo {
+    #[serde(default)]
     pub input_tokens: i32,
+    #[serde(default)]
     pub output_tokens: i32,
 }
 
@@ -353,8 +356,52 @@ fn create_streaming_request_body(
     })
 }
 
+// Helper function to build the messages for a prompt, prefilled with the start of the answer
+fn prefilled_messages(prompt: String) -> Vec<AnthropicMessage> {
+    vec![
+        AnthropicMessage {
+            role: "user".to_string(),
+            content: vec![AnthropicMessageContent {
+                content_type: "text".to_string(),
+                text: prompt,
+            }],
+        },
+        AnthropicMessage {
+            role: "assistant".to_string(),
+            content: vec![AnthropicMessageContent {
+                content_type: "text".to_string(),
+                text: PREFILL_TOKENS.to_string(),
+            }],
+        },
+    ]
+}
+
+/// Streaming request body for generating a new app from a prompt
+pub(crate) fn generation_request_body(model: &str, prompt: &str) -> serde_json::Value {
+    let system_message = include_str!("../prompts/app-renderer.txt");
+    create_streaming_request_body(model, system_message, prefilled_messages(prompt.to_string()))
+}
+
+/// Streaming request body for modifying existing app code according to a prompt
+pub(crate) fn modification_request_body(
+    model: &str,
+    existing_code: &str,
+    modification_prompt: &str,
+) -> serde_json::Value {
+    let system_message = format!("{}\n\n{}", include_str!("../prompts/code-modifier.txt"), include_str!("../prompts/app-renderer.txt"));
+
+    // Create a comprehensive prompt that includes both the existing code and modification request
+    let combined_prompt = format!(
+        "Here is the existing React component code that needs to be modified:\n\n```javascript\n{}\n```\n\nModification request: {}\n\nPlease output the complete modified component code.",
+        existing_code,
+        modification_prompt
+    );
+
+    create_streaming_request_body(model, &system_message, prefilled_messages(combined_prompt))
+}
+
 // Helper function to send streaming request to Anthropic API
-async fn send_streaming_request(
+pub(crate) async fn send_streaming_request(
     app_state: &AppState,
     body: &serde_json::Value,
 ) -> Result<reqwest::Response, String> {
@@ -369,14 +416,25 @@ async fn send_streaming_request(
     .map_err(|e| format!("Failed to send streaming request: {}", e))
 }
 
-// Helper function to process streaming response
-fn process_streaming_response(
+/// A parsed piece of an Anthropic streaming response
+pub(crate) enum StreamChunk {
+    /// The model started its message
+    Started,
+    /// Generated text, with the prefill tokens already prepended to the first token
+    Token(String),
+    /// The message finished, with the token usage collected along the way
+    Stopped(Option<UsageInfo>),
+    /// The API sent `[DONE]`
+    Done,
+    /// Reading the response failed
+    Failed(String),
+}
+
+// Helper function to parse the server-sent events of a streaming response
+pub(crate) fn parse_streaming_response(
     response: reqwest::Response,
-    completion_message: String,
-) -> impl Stream<Item = Result<Event, Infallible>> {
+) -> impl Stream<Item = StreamChunk> {
     async_stream::stream! {
-        yield Ok(Event::default().data("Streaming response from Anthropic API..."));
-
         use futures::StreamExt;
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
@@ -388,7 +446,7 @@ fn process_streaming_response(
                 Ok(bytes) => bytes,
                 Err(e) => {
                     tracing::error!("Error reading stream chunk: {}", e);
-                    yield Ok(Event::default().data(format!("Error: Stream error - {}", e)));
+                    yield StreamChunk::Failed(format!("Stream error - {}", e));
                     return;
                 }
             };
@@ -412,11 +470,10 @@ fn process_streaming_response(
                     continue;
                 }
 
-                if line.starts_with("data: ") {
-                    let data_part = &line[6..]; // Remove "data: " prefix
+                if let Some(data_part) = line.strip_prefix("data: ") {
 
                     if data_part == "[DONE]" {
-                        yield Ok(Event::default().data(&completion_message));
+                        yield StreamChunk::Done;
                         return;
                     }
 
@@ -425,7 +482,11 @@ fn process_streaming_response(
                         Ok(event) => {
                             match event.event_type.as_str() {
                                 "message_start" => {
-                                    yield Ok(Event::default().data("Starting message generation..."));
+                                    // The prompt's token count arrives with the message start
+                                    if let Some(usage) = event.message.and_then(|message| message.usage) {
+                                        usage_info = Some(usage);
+                                    }
+                                    yield StreamChunk::Started;
                                 }
                                 "content_block_delta" => {
                                     if let Some(delta) = event.delta {
@@ -436,41 +497,23 @@ fn process_streaming_response(
                                                 first_token = false;
                                             }
 
-                                            // Send the token as it arrives
-                                            let token_event = serde_json::json!({
-                                                "type": "token",
-                                                "text": text
-                                            });
-                                            yield Ok(Event::default().event("token").data(serde_json::to_string(&token_event).unwrap_or_default()));
-                                        }
-                                    }
-                                }
-                                "message_delta" => {
-                                    // Try to extract usage information from message_delta events
-                                    if let Ok(usage_event) = serde_json::from_str::<StreamingUsage>(data_part) {
-                                        if let Some(usage) = usage_event.usage {
-                                            usage_info = Some(usage);
+                                            yield StreamChunk::Token(text);
                                         }
                                     }
                                 }
                                 "message_stop" => {
-                                    // Send usage information before completing
-                                    if let Some(usage) = &usage_info {
-                                        let usage_event = serde_json::json!({
-                                            "type": "usage",
-                                            "input_tokens": usage.input_tokens,
-                                            "output_tokens": usage.output_tokens
-                                        });
-                                        yield Ok(Event::default().event("usage").data(serde_json::to_string(&usage_event).unwrap_or_default()));
-                                    }
-                                    yield Ok(Event::default().data(&completion_message));
+                                    yield StreamChunk::Stopped(usage_info.take());
                                     return;
                                 }
                                 _ => {
-                                    // Try to parse any event for usage information
+                                    // Try to parse any other event (usually message_delta) for usage information
                                     if let Ok(usage_event) = serde_json::from_str::<StreamingUsage>(data_part) {
                                         if let Some(usage) = usage_event.usage {
-                                            usage_info = Some(usage);
+                                            let input_tokens = usage_info.as_ref().map(|u| u.input_tokens).unwrap_or(0);
+                                            usage_info = Some(UsageInfo {
+                                                input_tokens: usage.input_tokens.max(input_tokens),
+                                                output_tokens: usage.output_tokens,
+                                            });
                                         }
                                     }
                                 }
@@ -484,6 +527,67 @@ fn process_streaming_response(
                 }
             }
         }
+    }
+}
+
+/// The SSE event announcing token usage at the end of a generation
+pub(crate) fn usage_event(usage: &UsageInfo) -> Event {
+    let usage_event = serde_json::json!({
+        "type": "usage",
+        "input_tokens": usage.input_tokens,
+        "output_tokens": usage.output_tokens
+    });
+    Event::default().event("usage").data(serde_json::to_string(&usage_event).unwrap_or_default())
+}
+
+/// The SSE event carrying one generated token
+pub(crate) fn token_event(text: &str) -> Event {
+    let token_event = serde_json::json!({
+        "type": "token",
+        "text": text
+    });
+    Event::default().event("token").data(serde_json::to_string(&token_event).unwrap_or_default())
+}
+
+// Helper function to process streaming response
+fn process_streaming_response(
+    response: reqwest::Response,
+    completion_message: String,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    async_stream::stream! {
+        yield Ok(Event::default().data("Streaming response from Anthropic API..."));
+
+        use futures::StreamExt;
+        let chunks = parse_streaming_response(response);
+        let mut chunks = std::pin::Pin::from(Box::new(chunks));
+
+        while let Some(chunk) = chunks.next().await {
+            match chunk {
+                StreamChunk::Started => {
+                    yield Ok(Event::default().data("Starting message generation..."));
+                }
+                StreamChunk::Token(text) => {
+                    // Send the token as it arrives
+                    yield Ok(token_event(&text));
+                }
+                StreamChunk::Stopped(usage) => {
+                    // Send usage information before completing
+                    if let Some(usage) = &usage {
+                        yield Ok(usage_event(usage));
+                    }
+                    yield Ok(Event::default().data(&completion_message));
+                    return;
+                }
+                StreamChunk::Done => {
+                    yield Ok(Event::default().data(&completion_message));
+                    return;
+                }
+                StreamChunk::Failed(e) => {
+                    yield Ok(Event::default().data(format!("Error: {}", e)));
+                    return;
+                }
+            }
+        }
 
         yield Ok(Event::default().data("Stream ended"));
     }
@@ -504,27 +608,8 @@ pub async fn generate_code_stream(
         yield Ok(Event::default().data("Preparing request to Anthropic API..."));
         tokio::time::sleep(Duration::from_millis(100)).await;
 
-        let system_message = include_str!("../prompts/app-renderer.txt");
-
-        let messages = vec![
-            AnthropicMessage {
-                role: "user".to_string(),
-                content: vec![AnthropicMessageContent {
-                    content_type: "text".to_string(),
-                    text: payload.prompt.clone(),
-                }],
-            },
-            AnthropicMessage {
-                role: "assistant".to_string(),
-                content: vec![AnthropicMessageContent {
-                    content_type: "text".to_string(),
-                    text: PREFILL_TOKENS.to_string(),
-                }],
-            },
-        ];
-
         let model = payload.model.as_deref().unwrap_or(Config::DEFAULT_MODEL);
-        let body = create_streaming_request_body(model, &system_message, messages);
+        let body = generation_request_body(model, &payload.prompt);
 
         yield Ok(Event::default().data("Sending request to Anthropic API..."));
 
@@ -568,34 +653,8 @@ pub async fn modify_code_stream(
         yield Ok(Event::default().data("Preparing request to Anthropic API..."));
         tokio::time::sleep(Duration::from_millis(100)).await;
 
-        let system_message = format!("{}\n\n{}", include_str!("../prompts/code-modifier.txt"), include_str!("../prompts/app-renderer.txt"));
-
-        // Create a comprehensive prompt that includes both the existing code and modification request
-        let combined_prompt = format!(
-            "Here is the existing React component code that needs to be modified:\n\n```javascript\n{}\n```\n\nModification request: {}\n\nPlease output the complete modified component code.",
-            payload.existing_code,
-            payload.modification_prompt
-        );
-
-        let messages = vec![
-            AnthropicMessage {
-                role: "user".to_string(),
-                content: vec![AnthropicMessageContent {
-                    content_type: "text".to_string(),
-                    text: combined_prompt,
-                }],
-            },
-            AnthropicMessage {
-                role: "assistant".to_string(),
-                content: vec![AnthropicMessageContent {
-                    content_type: "text".to_string(),
-                    text: PREFILL_TOKENS.to_string(),
-                }],
-            },
-        ];
-
         let model = payload.model.as_deref().unwrap_or(Config::DEFAULT_MODEL);
-        let body = create_streaming_request_body(model, &system_message, messages);
+        let body = modification_request_body(model, &payload.existing_code, &payload.modification_prompt);
 
         yield Ok(Event::default().data("Sending request to Anthropic API..."));
 
@@ -713,3 +772,62 @@ pub async fn generate_metadata_from_prompt(
 
     Ok(metadata)
 }
+
+/// Ask the model for a short, user-facing changelog of a diff between two versions
+pub async fn generate_changelog(
+    app_state: &AppState,
+    from_version: i32,
+    to_version: i32,
+    prompt: &str,
+    diff: &str,
+    model: &str,
+) -> Result<String, StatusCode> {
+    let changelog_prompt = format!(
+        include_str!("../prompts/changelog-writer.txt"),
+        to_version, from_version, prompt, diff
+    );
+
+    let body = serde_json::json!({
+        "model": model,
+        "max_tokens": 512,
+        "temperature": 0.3,
+        "messages": [AnthropicMessage {
+            role: "user".to_string(),
+            content: vec![AnthropicMessageContent {
+                content_type: "text".to_string(),
+                text: changelog_prompt,
+            }],
+        }],
+    });
+
+    let response = anthropic_request(
+        &app_state.client,
+        RequestMethod::Post,
+        &Config::anthropic_messages_url(),
+        Some(&body),
+        ResponseHandling::Standard,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to send request to Anthropic: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let anthropic_response: AnthropicResponse = response.json().await.map_err(|e| {
+        tracing::error!("Failed to parse Anthropic response: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let text = anthropic_response
+        .content
+        .as_ref()
+        .and_then(|c| c.first())
+        .map(|c| c.text.trim().to_string())
+        .unwrap_or_default();
+    if text.is_empty() {
+        tracing::error!("No content in changelog response");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(text)
+}
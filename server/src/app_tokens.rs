@@ -0,0 +1,89 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::AppToken;
+
+impl Database {
+    pub(crate) async fn create_app_tokens_table(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS app_tokens (
+                token TEXT PRIMARY KEY,
+                app_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_app_tokens_app_id ON app_tokens(app_id)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Issue a token identifying one launched instance of an app
+    pub async fn issue_app_token(&self, app_id: &str) -> Result<AppToken, sqlx::Error> {
+        let token = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(Config::app_token_ttl_secs() as i64);
+
+        // Drop tokens of instances that were closed long ago
+        sqlx::query("DELETE FROM app_tokens WHERE expires_at < ?")
+            .bind(now.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("INSERT INTO app_tokens (token, app_id, created_at, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(&token)
+            .bind(app_id)
+            .bind(now.to_rfc3339())
+            .bind(expires_at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(AppToken {
+            token,
+            app_id: app_id.to_string(),
+            expires_at,
+        })
+    }
+
+    /// The app a token was issued to, if it is valid and not expired
+    pub async fn resolve_app_token(&self, token: &str) -> Result<Option<String>, sqlx::Error> {
+        let row = sqlx::query("SELECT app_id, expires_at FROM app_tokens WHERE token = ?")
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        // A token whose expiry can't be read is treated as expired rather than trusted
+        let expires_at = DateTime::parse_from_rfc3339(row.get("expires_at"))
+            .map(|expires_at| expires_at.with_timezone(&Utc));
+        match expires_at {
+            Ok(expires_at) if expires_at >= Utc::now() => {}
+            _ => return Ok(None),
+        }
+
+        Ok(Some(row.get("app_id")))
+    }
+
+    /// Revoke every token issued to an app, e.g. once it is uninstalled
+    pub async fn revoke_app_tokens(&self, app_id: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM app_tokens WHERE app_id = ?")
+            .bind(app_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
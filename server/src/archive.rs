@@ -0,0 +1,150 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use crate::sources::sha256_hex;
+
+/// Name of the manifest entry at the start of every archive
+pub const MANIFEST_PATH: &str = "manifest.json";
+
+/// Most bytes an archive may unpack to, so a small gzip body can't expand without bound
+pub const MAX_UNPACKED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Most bytes an uploaded archive may take before unpacking
+pub const MAX_ARCHIVE_BYTES: usize = MAX_UNPACKED_BYTES as usize;
+
+/// Most bytes a single file in an archive may unpack to
+pub const MAX_ENTRY_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The body isn't a readable gzipped tar
+    Unreadable(String),
+    /// There is no manifest, or it isn't valid JSON
+    MissingManifest,
+    /// A file is missing, unlisted, or doesn't match its checksum
+    Checksum(String),
+    /// A file, or the archive as a whole, unpacks to more than the limits allow
+    TooLarge(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Unreadable(e) => write!(f, "unreadable archive: {}", e),
+            ArchiveError::MissingManifest => write!(f, "archive has no valid {}", MANIFEST_PATH),
+            ArchiveError::Checksum(path) => write!(f, "checksum mismatch for {}", path),
+            ArchiveError::TooLarge(path) => write!(f, "{} unpacks to too many bytes", path),
+        }
+    }
+}
+
+/// Pack `files` into a gzipped tar.
+///
+/// `manifest` is written first as `manifest.json`, with a `files` list giving each
+/// file's path, size and SHA-256 added to it.
+pub fn build_archive(mut manifest: Value, files: Vec<(String, Vec<u8>)>) -> std::io::Result<Vec<u8>> {
+    let listing: Vec<Value> = files
+        .iter()
+        .map(|(path, contents)| {
+            serde_json::json!({
+                "path": path,
+                "size": contents.len(),
+                "sha256": sha256_hex(contents)
+            })
+        })
+        .collect();
+    if let Some(manifest_obj) = manifest.as_object_mut() {
+        manifest_obj.insert("files".to_string(), Value::Array(listing));
+    }
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest).unwrap();
+
+    let mtime = chrono::Utc::now().timestamp().max(0) as u64;
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (path, contents) in std::iter::once((MANIFEST_PATH.to_string(), manifest_bytes)).chain(files) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, &path, contents.as_slice())?;
+    }
+
+    builder.into_inner()?.finish()
+}
+
+/// Unpack an archive made by [`build_archive`], checking every file against the manifest.
+///
+/// Returns the manifest and the files keyed by path, without the manifest itself. Reading
+/// stops as soon as a file passes [`MAX_ENTRY_BYTES`] or the archive [`MAX_UNPACKED_BYTES`].
+pub fn read_archive(bytes: &[u8]) -> Result<(Value, BTreeMap<String, Vec<u8>>), ArchiveError> {
+    // The tar reader also decompresses headers and padding, which the per-file count misses
+    let unpacked = GzDecoder::new(bytes).take(MAX_UNPACKED_BYTES + 1);
+    let mut archive = tar::Archive::new(unpacked);
+    let mut files = BTreeMap::new();
+    let mut total: u64 = 0;
+
+    let entries = archive
+        .entries()
+        .map_err(|e| ArchiveError::Unreadable(e.to_string()))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| ArchiveError::Unreadable(e.to_string()))?;
+        let path = entry
+            .path()
+            .map_err(|e| ArchiveError::Unreadable(e.to_string()))?
+            .to_string_lossy()
+            .to_string();
+        if entry.size() > MAX_ENTRY_BYTES {
+            return Err(ArchiveError::TooLarge(path));
+        }
+        total += entry.size();
+        if total > MAX_UNPACKED_BYTES {
+            return Err(ArchiveError::TooLarge("archive".to_string()));
+        }
+
+        let mut contents = Vec::new();
+        entry
+            .take(MAX_ENTRY_BYTES + 1)
+            .read_to_end(&mut contents)
+            .map_err(|e| ArchiveError::Unreadable(e.to_string()))?;
+        files.insert(path, contents);
+    }
+    if archive.into_inner().limit() == 0 {
+        return Err(ArchiveError::TooLarge("archive".to_string()));
+    }
+
+    let manifest: Value = files
+        .remove(MANIFEST_PATH)
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or(ArchiveError::MissingManifest)?;
+
+    let listing = manifest
+        .get("files")
+        .and_then(|v| v.as_array())
+        .ok_or(ArchiveError::MissingManifest)?;
+    for listed in listing {
+        let path = listed.get("path").and_then(|v| v.as_str()).unwrap_or("");
+        let expected = listed.get("sha256").and_then(|v| v.as_str()).unwrap_or("");
+        match files.get(path) {
+            Some(contents) if sha256_hex(contents) == expected => {}
+            _ => return Err(ArchiveError::Checksum(path.to_string())),
+        }
+    }
+    if files.len() != listing.len() {
+        // Something was added that the manifest doesn't vouch for
+        let unlisted = files
+            .keys()
+            .find(|path| {
+                !listing
+                    .iter()
+                    .any(|listed| listed.get("path").and_then(|v| v.as_str()) == Some(path.as_str()))
+            })
+            .cloned()
+            .unwrap_or_default();
+        return Err(ArchiveError::Checksum(unlisted));
+    }
+
+    Ok((manifest, files))
+}
@@ -0,0 +1,504 @@
+use aide::OperationInput;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use sqlx::{QueryBuilder, Row, Sqlite};
+
+use crate::database::{Database, SYSTEM_COLLECTIONS};
+use crate::models::{App, AppManifest, CollectionAccess, CollectionCapability, Document};
+use crate::sources::sha256_hex;
+use crate::AppState;
+
+/// Header carrying the token of the app instance a host API request is made for
+pub const APP_TOKEN_HEADER: &str = "x-app-token";
+
+/// The host API: routes app instances may call, which check who is calling with `AppCaller`
+const HOST_API_PATHS: &[&str] = &["/api/db", "/api/query", "/api/reset", "/api/snapshots"];
+
+/// Separates an app's id from the name of a collection private to it
+pub const NAMESPACE_SEPARATOR: char = ':';
+
+/// Collection name standing for every collection except the server's own
+pub const ANY_COLLECTION: &str = "*";
+
+const READ_METHODS: &[&str] = &["get", "list"];
+const WRITE_METHODS: &[&str] = &["create", "update", "delete"];
+
+impl AppManifest {
+    pub fn is_empty(&self) -> bool {
+        self.collections.is_empty() && self.storage.is_none() && !self.payments && self.network.is_empty()
+    }
+
+    /// Where `app_id` keeps the collection it calls `collection`, if this manifest allows
+    /// `access` to it: its own namespace, unless the collection is shared or only reachable
+    /// through `*`
+    pub fn storage_collection(
+        &self,
+        app_id: &str,
+        collection: &str,
+        access: CollectionAccess,
+    ) -> Option<String> {
+        if SYSTEM_COLLECTIONS.contains(&collection) {
+            return None;
+        }
+        // A collection listed by name is governed by that entry alone
+        if let Some(listed) = self.collections.iter().find(|c| c.name == collection) {
+            return (listed.access >= access).then(|| {
+                if listed.shared {
+                    collection.to_string()
+                } else {
+                    namespaced_collection(app_id, collection)
+                }
+            });
+        }
+        self.collections
+            .iter()
+            .any(|c| c.name == ANY_COLLECTION && c.access >= access)
+            .then(|| collection.to_string())
+    }
+
+    /// Whether everything `other` asks for is already allowed by this manifest
+    pub fn covers(&self, other: &AppManifest) -> bool {
+        let collections = other.collections.iter().all(|wanted| {
+            self.collections
+                .iter()
+                .filter(|c| {
+                    (c.name == wanted.name && c.shared == wanted.shared)
+                        || (c.name == ANY_COLLECTION && wanted.shared)
+                })
+                .any(|c| c.access >= wanted.access)
+        });
+        // No storage limit means the app may store as much as it likes
+        let storage = match (&self.storage, &other.storage) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(granted), Some(wanted)) => wanted.max_bytes <= granted.max_bytes,
+        };
+        collections
+            && storage
+            && (self.payments || !other.payments)
+            && other.network.iter().all(|host| self.network.contains(host))
+    }
+
+    /// Add a collection, keeping the broader access and sharing if it is already listed
+    pub fn add_collection(&mut self, name: &str, access: CollectionAccess, shared: bool) {
+        match self.collections.iter_mut().find(|c| c.name == name) {
+            Some(existing) => {
+                existing.access = existing.access.max(access);
+                existing.shared |= shared;
+            }
+            None => self.collections.push(CollectionCapability {
+                name: name.to_string(),
+                access,
+                shared,
+            }),
+        }
+    }
+
+    /// One human-readable line per capability, for asking the user's consent
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .collections
+            .iter()
+            .map(|c| {
+                let what = if c.name == ANY_COLLECTION {
+                    "the data of all your apps".to_string()
+                } else if c.shared {
+                    format!("the \"{}\" data shared between apps", c.name)
+                } else {
+                    format!("its \"{}\" data", c.name)
+                };
+                match c.access {
+                    CollectionAccess::Read => format!("Read {}", what),
+                    CollectionAccess::ReadWrite => format!("Read and change {}", what),
+                }
+            })
+            .collect();
+        if let Some(storage) = &self.storage {
+            lines.push(format!("Store up to {} KB of data", storage.max_bytes.div_ceil(1024)));
+        }
+        if self.payments {
+            lines.push("Take payments".to_string());
+        }
+        for host in &self.network {
+            lines.push(format!("Connect to {}", host));
+        }
+        lines
+    }
+}
+
+/// Check a manifest supplied with a release, normalizing names and dropping duplicates
+pub fn validate_manifest(manifest: AppManifest) -> Result<AppManifest, StatusCode> {
+    let mut valid = AppManifest {
+        storage: manifest.storage,
+        payments: manifest.payments,
+        ..Default::default()
+    };
+    for collection in &manifest.collections {
+        let name = collection.name.trim();
+        if name.is_empty() || name.contains(NAMESPACE_SEPARATOR) || SYSTEM_COLLECTIONS.contains(&name) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        valid.add_collection(name, collection.access, collection.shared);
+    }
+    for host in &manifest.network {
+        let host = host.trim().to_lowercase();
+        let name = host.strip_prefix("*.").unwrap_or(&host);
+        let valid_host = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':');
+        if !valid_host {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if !valid.network.contains(&host) {
+            valid.network.push(host);
+        }
+    }
+    Ok(valid)
+}
+
+/// Work out the capabilities an app needs from the collections and hosts its source names
+/// literally, e.g. `hostAPI.db.list("notes")` or `fetch("https://api.example.com/...")`
+pub fn infer_manifest(source_code: &str) -> AppManifest {
+    let mut manifest = AppManifest::default();
+
+    let mut rest = source_code;
+    while let Some(pos) = rest.find("db.") {
+        rest = &rest[pos + 3..];
+        let method_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let method = &rest[..method_len];
+        let access = if READ_METHODS.contains(&method) {
+            CollectionAccess::Read
+        } else if WRITE_METHODS.contains(&method) {
+            CollectionAccess::ReadWrite
+        } else {
+            continue;
+        };
+        let Some(args) = rest[method_len..].trim_start().strip_prefix('(') else {
+            continue;
+        };
+        if let Some(name) = string_literal(args.trim_start()) {
+            if !name.is_empty() && !name.contains(NAMESPACE_SEPARATOR) && !SYSTEM_COLLECTIONS.contains(&name) {
+                manifest.add_collection(name, access, false);
+            }
+        }
+    }
+
+    let mut rest = source_code;
+    while let Some(pos) = rest.find("fetch(") {
+        rest = &rest[pos + 6..];
+        let Some(url) = string_literal(rest.trim_start()) else {
+            continue;
+        };
+        let Some(after_scheme) = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")) else {
+            continue;
+        };
+        let host = after_scheme
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        if !host.is_empty() && !manifest.network.contains(&host) {
+            manifest.network.push(host);
+        }
+    }
+
+    manifest
+}
+
+/// Contents of a JS string literal at the start of `text`, unless it is built dynamically
+fn string_literal(text: &str) -> Option<&str> {
+    let quote = text.chars().next().filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let body = &text[1..];
+    let end = body.find(quote)?;
+    let literal = &body[..end];
+    if literal.contains('\\') || literal.contains("${") {
+        return None;
+    }
+    Some(literal)
+}
+
+/// The capabilities an app asks for: its release's manifest, or what its source uses for
+/// apps from before manifests existed.
+///
+/// Those older apps keep using the shared collections their data is already in.
+pub fn declared_manifest(app: &App) -> AppManifest {
+    app.manifest.clone().unwrap_or_else(|| {
+        let mut manifest = infer_manifest(app.source_code.as_deref().unwrap_or(""));
+        for collection in &mut manifest.collections {
+            collection.shared = true;
+        }
+        manifest
+    })
+}
+
+/// Name under which a collection private to `app_id` is stored
+pub fn namespaced_collection(app_id: &str, collection: &str) -> String {
+    format!("{}{}{}", app_id, NAMESPACE_SEPARATOR, collection)
+}
+
+/// The capabilities enforced on an app's host API requests: only what the user granted, so
+/// an app that hasn't been granted anything yet can't do anything
+pub fn effective_permissions(app: &App) -> AppManifest {
+    if !app.installed {
+        return AppManifest::default();
+    }
+    app.granted.clone().unwrap_or_default()
+}
+
+/// `connect-src` sources for a Content-Security-Policy limiting an app to its network hosts
+pub fn connect_sources(manifest: &AppManifest) -> Vec<String> {
+    manifest.network.iter().map(|host| format!("https://{}", host)).collect()
+}
+
+/// The app instance a host API request is made for, identified by the token in the
+/// `X-App-Token` header.
+///
+/// Without an app token the request must carry the dashboard token as `Authorization: Bearer`,
+/// and is then the dashboard's own (`None`) and isn't restricted. Anything else is refused
+/// with `401`.
+pub struct AppCaller(pub Option<CallerApp>);
+
+pub struct CallerApp {
+    pub app_id: String,
+    pub permissions: AppManifest,
+}
+
+impl AppCaller {
+    /// Check the caller may access `collection`, returning the collection its documents are
+    /// stored in
+    pub fn authorize(&self, collection: &str, access: CollectionAccess) -> Result<String, StatusCode> {
+        let Some(caller) = &self.0 else {
+            return Ok(collection.to_string());
+        };
+        caller
+            .permissions
+            .storage_collection(&caller.app_id, collection, access)
+            .ok_or_else(|| {
+                tracing::warn!("App {} was refused access to {}", caller.app_id, collection);
+                StatusCode::FORBIDDEN
+            })
+    }
+
+    /// Check the caller may access the collection stored as `stored`, for requests that carry
+    /// storage names rather than the names the app uses
+    pub fn authorize_stored(&self, stored: &str, access: CollectionAccess) -> Result<(), StatusCode> {
+        let Some(caller) = &self.0 else {
+            return Ok(());
+        };
+        let resolves = |name: &str| {
+            caller
+                .permissions
+                .storage_collection(&caller.app_id, name, access)
+                .is_some_and(|resolved| resolved == stored)
+        };
+        let prefix = namespaced_collection(&caller.app_id, "");
+        if stored.strip_prefix(&prefix).is_some_and(resolves) || resolves(stored) {
+            Ok(())
+        } else {
+            tracing::warn!("App {} was refused access to {}", caller.app_id, stored);
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+
+    /// Refuse requests made for any app, for routes only the dashboard may use
+    pub fn require_dashboard(&self) -> Result<(), StatusCode> {
+        match &self.0 {
+            Some(_) => Err(StatusCode::FORBIDDEN),
+            None => Ok(()),
+        }
+    }
+
+    /// The collections the app can read, under the names it uses for them
+    pub fn visible_collections(&self, collections: Vec<String>) -> Vec<String> {
+        let Some(caller) = &self.0 else {
+            return collections;
+        };
+        let readable = |name: &str, stored: &str| {
+            caller
+                .permissions
+                .storage_collection(&caller.app_id, name, CollectionAccess::Read)
+                .is_some_and(|resolved| resolved == stored)
+        };
+        let prefix = namespaced_collection(&caller.app_id, "");
+        collections
+            .into_iter()
+            .filter_map(|stored| match stored.strip_prefix(&prefix) {
+                Some(name) if readable(name, &stored) => Some(name.to_string()),
+                _ if readable(&stored, &stored) => Some(stored),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Make sure writing `data` to a document of the stored `collection` keeps the app within
+    /// its storage limit
+    pub async fn check_storage(
+        &self,
+        database: &Database,
+        collection: &str,
+        replacing_id: Option<&str>,
+        data: &Value,
+    ) -> Result<(), StatusCode> {
+        let Some(caller) = &self.0 else {
+            return Ok(());
+        };
+        let Some(storage) = &caller.permissions.storage else {
+            return Ok(());
+        };
+
+        let used = database
+            .app_data_bytes(&caller.app_id, &caller.permissions, replacing_id.map(|id| (collection, id)))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to measure app storage: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        let adding = serde_json::to_string(data).map(|s| s.len() as u64).unwrap_or(0);
+        if used + adding > storage.max_bytes {
+            return Err(StatusCode::INSUFFICIENT_STORAGE);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AppCaller {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, app_state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(header) = parts.headers.get(APP_TOKEN_HEADER) else {
+            return match bearer_token(&parts.headers) {
+                Some(token) if is_dashboard_token(token, &app_state.dashboard_token) => Ok(AppCaller(None)),
+                _ => Err(StatusCode::UNAUTHORIZED),
+            };
+        };
+        let token = header.to_str().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+        let app_id = app_state
+            .database
+            .resolve_app_token(token)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to look up app token: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let app_doc: Option<Document> = app_state
+            .database
+            .list_documents_where_in("apps", "id", std::slice::from_ref(&app_id))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to look up calling app: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .pop();
+        let app: App = app_doc.ok_or(StatusCode::FORBIDDEN)?.into();
+
+        Ok(AppCaller(Some(CallerApp {
+            permissions: effective_permissions(&app),
+            app_id,
+        })))
+    }
+}
+
+impl OperationInput for AppCaller {}
+
+/// Middleware guarding every `/api` route outside the host API, which only the dashboard may
+/// use: requests need the dashboard token (`401` otherwise), and requests made for an app are
+/// refused (`403`) even when they carry it.
+pub async fn require_dashboard_token(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    // Nested routers see their path without the `/api` prefix
+    let path = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path(),
+        None => request.uri().path(),
+    };
+    let host_api = HOST_API_PATHS
+        .iter()
+        .any(|prefix| path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')));
+    if host_api {
+        return Ok(next.run(request).await);
+    }
+
+    if request.headers().contains_key(APP_TOKEN_HEADER) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match bearer_token(request.headers()) {
+        Some(token) if is_dashboard_token(token, &app_state.dashboard_token) => Ok(next.run(request).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// The token in an `Authorization: Bearer` header
+fn bearer_token(headers: &header::HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Compare digests rather than the tokens themselves, so the time taken says nothing about
+/// how much of a guess was right
+fn is_dashboard_token(token: &str, dashboard_token: &str) -> bool {
+    sha256_hex(token.as_bytes()) == sha256_hex(dashboard_token.as_bytes())
+}
+
+impl Database {
+    /// Bytes of document data in the collections `manifest` lets an app write, leaving out
+    /// one document that is about to be replaced
+    pub async fn app_data_bytes(
+        &self,
+        app_id: &str,
+        manifest: &AppManifest,
+        excluding: Option<(&str, &str)>,
+    ) -> Result<u64, sqlx::Error> {
+        let writable: Vec<String> = manifest
+            .collections
+            .iter()
+            .filter(|c| c.access == CollectionAccess::ReadWrite)
+            .map(|c| match c.shared || c.name == ANY_COLLECTION {
+                true => c.name.clone(),
+                false => namespaced_collection(app_id, &c.name),
+            })
+            .collect();
+        if writable.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COALESCE(SUM(LENGTH(CAST(data AS BLOB))), 0) AS bytes FROM documents WHERE ");
+        let (names, negate): (Vec<String>, bool) = if writable.iter().any(|name| name == ANY_COLLECTION) {
+            (SYSTEM_COLLECTIONS.iter().map(|name| name.to_string()).collect(), true)
+        } else {
+            (writable, false)
+        };
+        query.push(if negate { "collection NOT IN (" } else { "collection IN (" });
+        let mut separated = query.separated(", ");
+        for name in names {
+            separated.push_bind(name);
+        }
+        separated.push_unseparated(")");
+        if let Some((collection, id)) = excluding {
+            query.push(" AND NOT (collection = ");
+            query.push_bind(collection);
+            query.push(" AND id = ");
+            query.push_bind(id);
+            query.push(")");
+        }
+
+        let row = query.build().fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>("bytes") as u64)
+    }
+}
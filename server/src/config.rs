@@ -13,6 +13,41 @@ impl Config {
     /// Default model for Anthropic API calls
     pub const DEFAULT_MODEL: &'static str = "claude-3-haiku-20240307";
 
+    /// Default SQLite journal mode (WAL lets readers run alongside a writer)
+    pub const DEFAULT_SQLITE_JOURNAL_MODE: &'static str = "wal";
+
+    /// Default SQLite synchronous level (safe with WAL, much faster than FULL)
+    pub const DEFAULT_SQLITE_SYNCHRONOUS: &'static str = "normal";
+
+    /// Default time in milliseconds a connection waits on a locked database
+    pub const DEFAULT_SQLITE_BUSY_TIMEOUT_MS: u64 = 5000;
+
+    /// Default maximum number of pooled SQLite connections
+    pub const DEFAULT_SQLITE_POOL_SIZE: u32 = 5;
+
+    /// Default number of database snapshots kept
+    pub const DEFAULT_SNAPSHOT_RETENTION: u32 = 20;
+
+    /// Default seconds between background version pruning runs
+    pub const DEFAULT_VERSION_PRUNE_INTERVAL_SECS: u64 = 3600;
+
+    /// Default seconds an app token stays valid
+    pub const DEFAULT_APP_TOKEN_TTL_SECS: u64 = 43200;
+
+    /// Default milliseconds app source may run for in a smoke test
+    pub const DEFAULT_SMOKE_TEST_TIMEOUT_MS: u64 = 2000;
+
+    /// Default React build loaded by standalone app pages
+    pub const DEFAULT_REACT_URL: &'static str =
+        "https://unpkg.com/react@18.3.1/umd/react.production.min.js";
+
+    /// Default ReactDOM build loaded by standalone app pages
+    pub const DEFAULT_REACT_DOM_URL: &'static str =
+        "https://unpkg.com/react-dom@18.3.1/umd/react-dom.production.min.js";
+
+    /// Default seconds downloading React for exported apps may take
+    pub const DEFAULT_REACT_DOWNLOAD_TIMEOUT_SECS: u64 = 15;
+
     /// Get the Anthropic API key from environment
     pub fn anthropic_api_key() -> Result<String, String> {
         env::var("ANTHROPIC_API_KEY").map_err(|_| {
@@ -29,4 +64,93 @@ impl Config {
     pub fn anthropic_messages_url() -> String {
         format!("{}/messages", Self::ANTHROPIC_API_BASE_URL)
     }
-}
\ No newline at end of file
+
+    /// Get the SQLite journal mode (`SQLITE_JOURNAL_MODE`)
+    pub fn sqlite_journal_mode() -> String {
+        env::var("SQLITE_JOURNAL_MODE")
+            .unwrap_or_else(|_| Self::DEFAULT_SQLITE_JOURNAL_MODE.to_string())
+    }
+
+    /// Get the SQLite synchronous level (`SQLITE_SYNCHRONOUS`)
+    pub fn sqlite_synchronous() -> String {
+        env::var("SQLITE_SYNCHRONOUS")
+            .unwrap_or_else(|_| Self::DEFAULT_SQLITE_SYNCHRONOUS.to_string())
+    }
+
+    /// Get the SQLite busy timeout in milliseconds (`SQLITE_BUSY_TIMEOUT_MS`)
+    pub fn sqlite_busy_timeout_ms() -> u64 {
+        env::var("SQLITE_BUSY_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_SQLITE_BUSY_TIMEOUT_MS)
+    }
+
+    /// Get the maximum number of pooled SQLite connections (`SQLITE_POOL_SIZE`)
+    pub fn sqlite_pool_size() -> u32 {
+        env::var("SQLITE_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_SQLITE_POOL_SIZE)
+    }
+
+    /// Get the seconds between background version pruning runs (`VERSION_PRUNE_INTERVAL_SECS`);
+    /// 0 turns the background job off
+    pub fn version_prune_interval_secs() -> u64 {
+        env::var("VERSION_PRUNE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_VERSION_PRUNE_INTERVAL_SECS)
+    }
+
+    /// Get the number of most recent database snapshots kept (`SNAPSHOT_RETENTION`); 0 keeps
+    /// every snapshot
+    pub fn snapshot_retention() -> u32 {
+        env::var("SNAPSHOT_RETENTION")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_SNAPSHOT_RETENTION)
+    }
+
+    /// Get the React UMD build standalone app pages load (`REACT_URL`)
+    pub fn react_url() -> String {
+        env::var("REACT_URL").unwrap_or_else(|_| Self::DEFAULT_REACT_URL.to_string())
+    }
+
+    /// Get the ReactDOM UMD build standalone app pages load (`REACT_DOM_URL`)
+    pub fn react_dom_url() -> String {
+        env::var("REACT_DOM_URL").unwrap_or_else(|_| Self::DEFAULT_REACT_DOM_URL.to_string())
+    }
+
+    /// Get the seconds downloading React for exported apps may take
+    /// (`REACT_DOWNLOAD_TIMEOUT_SECS`)
+    pub fn react_download_timeout_secs() -> u64 {
+        env::var("REACT_DOWNLOAD_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_REACT_DOWNLOAD_TIMEOUT_SECS)
+    }
+
+    /// Get the seconds an app token stays valid (`APP_TOKEN_TTL_SECS`)
+    pub fn app_token_ttl_secs() -> u64 {
+        env::var("APP_TOKEN_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_APP_TOKEN_TTL_SECS)
+    }
+
+    /// Get the token the dashboard authenticates with (`DASHBOARD_TOKEN`), if one is set
+    pub fn dashboard_token() -> Option<String> {
+        env::var("DASHBOARD_TOKEN")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Get the milliseconds app source may run for in a smoke test (`SMOKE_TEST_TIMEOUT_MS`)
+    pub fn smoke_test_timeout_ms() -> u64 {
+        env::var("SMOKE_TEST_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::DEFAULT_SMOKE_TEST_TIMEOUT_MS)
+    }
+}
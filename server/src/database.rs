@@ -1,19 +1,90 @@
 use chrono::{DateTime, Utc};
 use serde_json::Value;
-use sqlx::{Column, Pool, Row, Sqlite, SqlitePool};
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous,
+};
+use sqlx::{Column, Pool, Row, Sqlite};
+use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::config::Config;
 use crate::models::{Document, QueryResult};
+use crate::sources::{save_source, split_source};
+
+/// Connection tuning applied to every pooled SQLite connection
+#[derive(Debug, Clone)]
+pub struct DatabaseOptions {
+    pub journal_mode: SqliteJournalMode,
+    pub synchronous: SqliteSynchronous,
+    pub busy_timeout: Duration,
+    pub pool_size: u32,
+    pub foreign_keys: bool,
+}
+
+impl DatabaseOptions {
+    /// Build options from the `SQLITE_*` environment variables
+    pub fn from_env() -> Result<Self, sqlx::Error> {
+        Ok(DatabaseOptions {
+            journal_mode: SqliteJournalMode::from_str(&Config::sqlite_journal_mode())?,
+            synchronous: SqliteSynchronous::from_str(&Config::sqlite_synchronous())?,
+            busy_timeout: Duration::from_millis(Config::sqlite_busy_timeout_ms()),
+            pool_size: Config::sqlite_pool_size(),
+            foreign_keys: true,
+        })
+    }
+}
+
+/// Collections the server itself manages; apps can't claim them as their data
+pub const SYSTEM_COLLECTIONS: &[&str] = &[
+    "apps",
+    "dashboard_layouts",
+    "project_versions",
+    "projects",
+    "releases",
+    "templates",
+];
 
 #[derive(Debug, Clone)]
 pub struct Database {
-    pool: Pool<Sqlite>,
+    pub(crate) pool: Pool<Sqlite>,
 }
 
 impl Database {
     pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
-        let pool = SqlitePool::connect(database_url).await?;
+        Self::with_options(database_url, DatabaseOptions::from_env()?).await
+    }
+
+    pub async fn with_options(
+        database_url: &str,
+        options: DatabaseOptions,
+    ) -> Result<Self, sqlx::Error> {
+        let connect_options = SqliteConnectOptions::from_str(database_url)?
+            .journal_mode(options.journal_mode)
+            .synchronous(options.synchronous)
+            .busy_timeout(options.busy_timeout)
+            .foreign_keys(options.foreign_keys);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(options.pool_size)
+            .connect_with(connect_options)
+            .await?;
+
+        let database = Database { pool };
 
+        database.create_documents_table().await?;
+        database.create_snapshot_tables().await?;
+        database.create_sources_table().await?;
+        database.create_app_tokens_table().await?;
+        database.migrate_inline_sources().await?;
+
+        // Seed default apps if none exist
+        database.seed_default_apps().await?;
+
+        Ok(database)
+    }
+
+    async fn create_documents_table(&self) -> Result<(), sqlx::Error> {
         // Create documents table if it doesn't exist
         sqlx::query(
             r#"
@@ -26,34 +97,43 @@ impl Database {
             )
             "#,
         )
-        .execute(&pool)
+        .execute(&self.pool)
         .await?;
 
         // Create indexes for better performance
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_collection ON documents(collection)")
-            .execute(&pool)
+            .execute(&self.pool)
             .await?;
 
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_created_at ON documents(created_at)")
-            .execute(&pool)
+            .execute(&self.pool)
             .await?;
 
-        let database = Database { pool };
-
-        // Seed default apps if none exist
-        database.seed_default_apps().await?;
+        // A project can never have two versions with the same number. Databases written
+        // before the index existed may already hold duplicates, which are renumbered first.
+        self.renumber_duplicate_versions().await?;
+        sqlx::query(
+            r#"
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_project_version_number
+            ON documents(json_extract(data, '$.project_id'), json_extract(data, '$.version_number'))
+            WHERE collection = 'project_versions'
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
 
-        Ok(database)
+        Ok(())
     }
 
     pub async fn create_document(
         &self,
         collection: &str,
-        data: Value,
+        mut data: Value,
     ) -> Result<Document, sqlx::Error> {
         let id = Uuid::new_v4().to_string();
         let now = Utc::now();
-        let data_str = serde_json::to_string(&data).unwrap();
+        let (stored, source) = split_source(collection, &mut data);
+        let data_str = serde_json::to_string(&stored).unwrap();
 
         let document = Document {
             id: id.clone(),
@@ -63,6 +143,11 @@ impl Database {
             updated_at: now,
         };
 
+        let mut tx = self.pool.begin().await?;
+        if let Some((hash, source_code)) = source {
+            save_source(&mut tx, &hash, &source_code).await?;
+        }
+
         sqlx::query(
             r#"
             INSERT INTO documents (id, collection, data, created_at, updated_at)
@@ -72,14 +157,57 @@ impl Database {
         .bind(&id)
         .bind(collection)
         .bind(&data_str)
-        .bind(&now.to_rfc3339())
-        .bind(&now.to_rfc3339())
-        .execute(&self.pool)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
         Ok(document)
     }
 
+    /// Insert several documents, possibly across collections, in one transaction
+    pub async fn create_documents(
+        &self,
+        documents: Vec<(String, Value)>,
+    ) -> Result<Vec<Document>, sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        let mut created = Vec::with_capacity(documents.len());
+
+        for (collection, mut data) in documents {
+            let id = Uuid::new_v4().to_string();
+            let (stored, source) = split_source(&collection, &mut data);
+            if let Some((hash, source_code)) = source {
+                save_source(&mut tx, &hash, &source_code).await?;
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO documents (id, collection, data, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&id)
+            .bind(&collection)
+            .bind(serde_json::to_string(&stored).unwrap())
+            .bind(now.to_rfc3339())
+            .bind(now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+            created.push(Document {
+                id,
+                collection,
+                data,
+                created_at: now,
+                updated_at: now,
+            });
+        }
+
+        tx.commit().await?;
+        Ok(created)
+    }
+
     pub async fn get_document(
         &self,
         collection: &str,
@@ -107,13 +235,16 @@ impl Database {
                     .unwrap()
                     .with_timezone(&Utc);
 
-                Ok(Some(Document {
+                let mut documents = [Document {
                     id: row.get("id"),
                     collection: row.get("collection"),
                     data,
                     created_at,
                     updated_at,
-                }))
+                }];
+                self.hydrate_sources(&mut documents).await?;
+                let [document] = documents;
+                Ok(Some(document))
             }
             None => Ok(None),
         }
@@ -123,10 +254,16 @@ impl Database {
         &self,
         collection: &str,
         id: &str,
-        data: Value,
+        mut data: Value,
     ) -> Result<Option<Document>, sqlx::Error> {
         let now = Utc::now();
-        let data_str = serde_json::to_string(&data).unwrap();
+        let (stored, source) = split_source(collection, &mut data);
+        let data_str = serde_json::to_string(&stored).unwrap();
+
+        let mut tx = self.pool.begin().await?;
+        if let Some((hash, source_code)) = source {
+            save_source(&mut tx, &hash, &source_code).await?;
+        }
 
         let affected_rows = sqlx::query(
             r#"
@@ -139,9 +276,57 @@ impl Database {
         .bind(now.to_rfc3339())
         .bind(collection)
         .bind(id)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?
         .rows_affected();
+        tx.commit().await?;
+
+        if affected_rows == 0 {
+            Ok(None)
+        } else {
+            self.get_document(collection, id).await
+        }
+    }
+
+    /// Set (`Some`) or remove (`None`) top-level fields of a document's data in place.
+    ///
+    /// Unlike `update_document` the rest of the stored data is left as it is, so fields
+    /// another request changed in the meantime aren't overwritten with stale values. Not for
+    /// `source_code`, which is stored apart from the document.
+    pub async fn patch_document(
+        &self,
+        collection: &str,
+        id: &str,
+        fields: Vec<(&str, Option<Value>)>,
+    ) -> Result<Option<Document>, sqlx::Error> {
+        let now = Utc::now();
+
+        let mut data = String::from("data");
+        for (_, value) in &fields {
+            data = match value {
+                Some(_) => format!("json_set({}, ?, json(?))", data),
+                None => format!("json_remove({}, ?)", data),
+            };
+        }
+        let sql = format!(
+            "UPDATE documents SET data = {}, updated_at = ? WHERE collection = ? AND id = ?",
+            data
+        );
+
+        let mut query = sqlx::query(&sql);
+        for (name, value) in fields {
+            query = query.bind(format!("$.\"{}\"", name));
+            if let Some(value) = value {
+                query = query.bind(serde_json::to_string(&value).unwrap());
+            }
+        }
+        let affected_rows = query
+            .bind(now.to_rfc3339())
+            .bind(collection)
+            .bind(id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
 
         if affected_rows == 0 {
             Ok(None)
@@ -190,7 +375,7 @@ impl Database {
         .fetch_all(&self.pool)
         .await?;
 
-        let documents: Vec<Document> = rows
+        let mut documents: Vec<Document> = rows
             .into_iter()
             .map(|row| {
                 let data: Value = serde_json::from_str(row.get("data")).unwrap();
@@ -210,6 +395,7 @@ impl Database {
                 }
             })
             .collect();
+        self.hydrate_sources(&mut documents).await?;
 
         let count_row = sqlx::query("SELECT COUNT(*) as count FROM documents WHERE collection = ?")
             .bind(collection)
@@ -281,40 +467,4 @@ impl Database {
 
         Ok(results)
     }
-
-    pub async fn reset_database(&self) -> Result<(), sqlx::Error> {
-        // Drop the documents table
-        sqlx::query("DROP TABLE IF EXISTS documents")
-            .execute(&self.pool)
-            .await?;
-
-        // Recreate the documents table with indexes
-        sqlx::query(
-            r#"
-            CREATE TABLE documents (
-                id TEXT PRIMARY KEY,
-                collection TEXT NOT NULL,
-                data TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        // Recreate indexes
-        sqlx::query("CREATE INDEX idx_collection ON documents(collection)")
-            .execute(&self.pool)
-            .await?;
-
-        sqlx::query("CREATE INDEX idx_created_at ON documents(created_at)")
-            .execute(&self.pool)
-            .await?;
-
-        // Re-seed default apps after reset
-        self.seed_default_apps().await?;
-
-        Ok(())
-    }
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+use similar::{ChangeTag, TextDiff};
+
+use crate::models::{DiffHunk, DiffLine, DiffStats};
+
+/// Line-based diff between two source files
+pub struct SourceDiff {
+    pub unified: String,
+    pub hunks: Vec<DiffHunk>,
+    pub stats: DiffStats,
+}
+
+/// Diff `old` against `new`, keeping `context` unchanged lines around each change
+pub fn diff_sources(
+    old: &str,
+    new: &str,
+    old_label: &str,
+    new_label: &str,
+    context: usize,
+) -> SourceDiff {
+    let diff = TextDiff::from_lines(old, new);
+
+    let unified = diff
+        .unified_diff()
+        .context_radius(context)
+        .header(old_label, new_label)
+        .to_string();
+
+    let mut stats = DiffStats {
+        insertions: 0,
+        deletions: 0,
+        unchanged: 0,
+        hunks: 0,
+    };
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => stats.insertions += 1,
+            ChangeTag::Delete => stats.deletions += 1,
+            ChangeTag::Equal => stats.unchanged += 1,
+        }
+    }
+
+    let mut udiff = diff.unified_diff();
+    udiff.context_radius(context);
+
+    let hunks: Vec<DiffHunk> = udiff
+        .iter_hunks()
+        .map(|hunk| {
+            let ops = hunk.ops();
+            let (first, last) = (&ops[0], &ops[ops.len() - 1]);
+            let old_start = first.old_range().start;
+            let new_start = first.new_range().start;
+            let old_lines = last.old_range().end - old_start;
+            let new_lines = last.new_range().end - new_start;
+
+            let lines = hunk
+                .iter_changes()
+                .map(|change| DiffLine {
+                    kind: match change.tag() {
+                        ChangeTag::Insert => "insert",
+                        ChangeTag::Delete => "delete",
+                        ChangeTag::Equal => "context",
+                    }
+                    .to_string(),
+                    content: change.value().trim_end_matches(['\r', '\n']).to_string(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                })
+                .collect();
+
+            DiffHunk {
+                header: hunk.header().to_string(),
+                // Unified diff line numbers are 1-based
+                old_start: old_start + 1,
+                old_lines,
+                new_start: new_start + 1,
+                new_lines,
+                lines,
+            }
+        })
+        .collect();
+
+    stats.hunks = hunks.len();
+
+    SourceDiff {
+        unified,
+        hunks,
+        stats,
+    }
+}
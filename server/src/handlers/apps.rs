@@ -6,7 +6,13 @@ use axum::{
 };
 use uuid::Uuid;
 
-use crate::models::{ListQuery, AppListResponse, AppListMeta, AppListLinks, AppResponse, AppResponseLinks, CreateAppRequest, UpdateAppSourceCodeRequest};
+use super::releases::{apply_release, find_releases, install_release, latest_releases, release_ref};
+use super::sources::SourceRejection;
+use crate::capabilities::{declared_manifest, effective_permissions, infer_manifest, namespaced_collection, AppCaller};
+use crate::database::SYSTEM_COLLECTIONS;
+use crate::listing::{parse_date_param, DocumentFilter, DocumentSort};
+use crate::models::{ListAppsQuery, App, AppListResponse, AppListMeta, AppListLinks, AppResponse, AppResponseLinks, AppPermissions, AppPermissionsLinks, AppPermissionsResponse, AppTokenResponse, AppUninstallMeta, AppUninstallResponse, ConsentRequest, CreateAppRequest, DowngradeAppRequest, Document, RemoveAppDataOptions, UpdateAppRequest, UpdateAppSourceCodeRequest};
+use crate::validation::validate_source;
 use crate::AppState;
 
 pub async fn create_app(
@@ -14,6 +20,9 @@ pub async fn create_app(
     JsonBody(req): JsonBody<CreateAppRequest>,
 ) -> Result<Json<AppResponse>, StatusCode> {
     let app_id = Uuid::new_v4().to_string();
+    let validation = req.source_code.as_deref().map(validate_source);
+    // What the source uses is declared, but nothing is granted until the user consents
+    let manifest = infer_manifest(req.source_code.as_deref().unwrap_or(""));
 
     let app_data = serde_json::json!({
         "id": app_id,
@@ -22,8 +31,11 @@ pub async fn create_app(
         "version": req.version,
         "price": req.price,
         "icon": req.icon,
-        "installed": 1,
+        "installed": true,
         "source_code": req.source_code,
+        "validation": validation,
+        "manifest": manifest,
+        "granted": null,
         "prompt": req.prompt,
         "model": req.model,
         "status": "draft",
@@ -51,14 +63,14 @@ pub async fn update_app_source_code(
     State(app_state): State<AppState>,
     Path(app_id): Path<String>,
     JsonBody(req): JsonBody<UpdateAppSourceCodeRequest>,
-) -> Result<Json<AppResponse>, StatusCode> {
+) -> Result<Json<AppResponse>, SourceRejection> {
     // First get the existing app
     let apps_result = app_state.database.list_documents("apps", Some(1000), Some(0)).await;
     let apps = match apps_result {
         Ok(result) => result,
         Err(e) => {
             tracing::error!("Failed to list apps: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
         }
     };
 
@@ -71,12 +83,23 @@ pub async fn update_app_source_code(
 
     let mut app_document = match app_document {
         Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
+        None => return Err(StatusCode::NOT_FOUND.into()),
     };
 
-    // Update the source_code field
+    // The app runs whatever is saved here, so broken source is refused with its diagnostics
+    let validation = validate_source(&req.source_code);
+    if validation.has_errors() {
+        return Err(SourceRejection::Invalid(validation));
+    }
+
+    // New source may use more than the user agreed to, so its grant starts over
+    let manifest = infer_manifest(&req.source_code);
     if let Some(data_obj) = app_document.data.as_object_mut() {
         data_obj.insert("source_code".to_string(), serde_json::Value::String(req.source_code));
+        data_obj.insert("validation".to_string(), serde_json::json!(validation));
+        data_obj.insert("manifest".to_string(), serde_json::json!(manifest));
+        data_obj.insert("granted".to_string(), serde_json::Value::Null);
+        data_obj.remove("granted_at");
     }
 
     match app_state.database.update_document("apps", &app_document.id, app_document.data).await {
@@ -89,33 +112,83 @@ pub async fn update_app_source_code(
             };
             Ok(Json(response))
         },
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into()),
         Err(e) => {
             tracing::error!("Failed to update app: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into())
         }
     }
 }
 
 pub async fn list_apps(
     State(app_state): State<AppState>,
-    Query(query): Query<ListQuery>,
+    Query(query): Query<ListAppsQuery>,
 ) -> Result<Json<AppListResponse>, StatusCode> {
     let limit = query.limit.unwrap_or(100);
     let offset = query.offset.unwrap_or(0);
 
+    let mut filter = DocumentFilter {
+        search: query.q.filter(|q| !q.trim().is_empty()),
+        status: query.status,
+        installed: query.installed,
+        created_after: parse_date_param(query.created_after).map_err(|_| StatusCode::BAD_REQUEST)?,
+        created_before: parse_date_param(query.created_before).map_err(|_| StatusCode::BAD_REQUEST)?,
+        updated_after: parse_date_param(query.updated_after).map_err(|_| StatusCode::BAD_REQUEST)?,
+        updated_before: parse_date_param(query.updated_before).map_err(|_| StatusCode::BAD_REQUEST)?,
+        ..Default::default()
+    };
+    if let Some(model) = query.model {
+        filter.equals.push(("model", model));
+    }
+    if let Some(min_price) = query.min_price {
+        filter.at_least.push(("price", min_price));
+    }
+    if let Some(max_price) = query.max_price {
+        filter.at_most.push(("price", max_price));
+    }
+    let sort = DocumentSort {
+        field: query.sort,
+        order: query.order,
+    };
+
     match app_state
         .database
-        .list_documents("apps", Some(limit), Some(offset))
+        .list_filtered_documents("apps", &filter, &sort, Some(limit), Some(offset))
         .await
     {
         Ok(result) => {
+            let project_ids: Vec<String> = result
+                .documents
+                .iter()
+                .filter_map(|doc| doc.data.get("project_id").and_then(|v| v.as_str()))
+                .map(|project_id| project_id.to_string())
+                .collect();
+            let latest = latest_releases(&app_state, &project_ids).await?;
+
+            let apps: Vec<App> = result
+                .documents
+                .into_iter()
+                .map(|doc| {
+                    let mut app: App = doc.into();
+                    let latest_release = app
+                        .project_id
+                        .as_ref()
+                        .and_then(|project_id| latest.get(project_id));
+                    if let Some(latest_release) = latest_release {
+                        mark_update_available(&mut app, latest_release);
+                    }
+                    app
+                })
+                .collect();
+            let updates_available = apps.iter().filter(|app| app.update_available).count();
+
             let response = AppListResponse {
-                data: result.documents.into_iter().map(|doc| doc.into()).collect(),
+                data: apps,
                 meta: AppListMeta {
                     count: result.count,
                     limit,
                     offset,
+                    updates_available,
                 },
                 links: AppListLinks {
                     self_link: format!("/api/apps?limit={}&offset={}", limit, offset),
@@ -129,4 +202,465 @@ pub async fn list_apps(
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
-}
\ No newline at end of file
+}
+
+pub async fn upgrade_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<Json<AppResponse>, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.clone().into();
+    let project_id = app.project_id.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let releases = find_releases(&app_state, &project_id).await?;
+    let latest = releases.first().map(release_ref).ok_or(StatusCode::NOT_FOUND)?;
+    if app.project_version == Some(latest.project_version) && app.version == latest.version {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let updated_doc = install_release(&app_state, app_doc, latest).await?;
+    Ok(Json(app_response(updated_doc)))
+}
+
+pub async fn downgrade_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+    JsonBody(req): JsonBody<DowngradeAppRequest>,
+) -> Result<Json<AppResponse>, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.clone().into();
+    let project_id = app.project_id.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let target = match req.release_id {
+        Some(release_id) => find_releases(&app_state, &project_id)
+            .await?
+            .iter()
+            .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&release_id))
+            .map(release_ref)
+            .ok_or(StatusCode::NOT_FOUND)?,
+        None => app.previous_release.ok_or(StatusCode::NOT_FOUND)?,
+    };
+
+    let updated_doc = install_release(&app_state, app_doc, target).await?;
+    Ok(Json(app_response(updated_doc)))
+}
+
+pub async fn get_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<Json<AppResponse>, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut response = app_response(app_doc);
+
+    if let Some(project_id) = response.data.project_id.clone() {
+        if let Some(latest_release) = find_releases(&app_state, &project_id).await?.first() {
+            mark_update_available(&mut response.data, latest_release);
+        }
+    }
+    Ok(Json(response))
+}
+
+/// Update an app's name, description, icon, price or data collections
+pub async fn update_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+    JsonBody(req): JsonBody<UpdateAppRequest>,
+) -> Result<Json<AppResponse>, StatusCode> {
+    let mut app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let data_collections = match req.data_collections {
+        Some(collections) => Some(validate_data_collections(collections)?),
+        None => None,
+    };
+    if req.price.is_some_and(|price| price < 0.0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Some(data_obj) = app_doc.data.as_object_mut() {
+        if let Some(name) = req.name {
+            data_obj.insert("name".to_string(), serde_json::Value::String(name));
+        }
+        if let Some(description) = req.description {
+            data_obj.insert("description".to_string(), serde_json::Value::String(description));
+        }
+        if let Some(icon) = req.icon {
+            data_obj.insert("icon".to_string(), serde_json::Value::String(icon));
+        }
+        if let Some(price) = req.price {
+            data_obj.insert("price".to_string(), serde_json::json!(price));
+        }
+        if let Some(data_collections) = data_collections {
+            data_obj.insert("data_collections".to_string(), serde_json::json!(data_collections));
+        }
+        data_obj.insert(
+            "updated_at".to_string(),
+            serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+
+    let updated_doc = save_app(&app_state, app_doc).await?;
+    Ok(Json(app_response(updated_doc)))
+}
+
+/// Delete an app, and with `remove_data` its data collections
+pub async fn delete_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+    Query(options): Query<RemoveAppDataOptions>,
+) -> Result<StatusCode, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.clone().into();
+
+    if options.remove_data {
+        remove_app_data(&app_state, &app).await?;
+    }
+    revoke_tokens(&app_state, &app.id).await?;
+
+    match app_state.database.delete_document("apps", &app_doc.id).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            tracing::error!("Failed to delete app: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Install an uninstalled app, switching a project's app to its latest release first.
+///
+/// An app that declares capabilities the user hasn't granted is only installed with
+/// `consent: true`, which grants them; otherwise the request fails with 428 and the client
+/// should show `GET /api/apps/:id/permissions` first.
+pub async fn install_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+    JsonBody(req): JsonBody<ConsentRequest>,
+) -> Result<Json<AppResponse>, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut app: App = app_doc.clone().into();
+    if app.installed {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let mut app_doc = app_doc;
+    if let Some(project_id) = app.project_id.clone() {
+        if let Some(latest) = find_releases(&app_state, &project_id).await?.first().map(release_ref) {
+            if app.project_version != Some(latest.project_version) || app.version != latest.version {
+                app_doc = apply_release(&app_state, app_doc, latest).await?;
+                app = app_doc.clone().into();
+            }
+        }
+    }
+
+    let app_doc = grant_declared_manifest(&app, app_doc, req.consent)?;
+    let updated_doc = set_installed(&app_state, app_doc, true).await?;
+    Ok(Json(app_response(updated_doc)))
+}
+
+/// Issue a token for a launched instance of an installed app.
+///
+/// Only the dashboard may ask, when it launches the app; it passes the token in, and the app
+/// sends it as `X-App-Token` on its host API requests, which are then limited to the app's
+/// permissions and collections.
+pub async fn issue_app_token(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+    Path(app_id): Path<String>,
+) -> Result<Json<AppTokenResponse>, StatusCode> {
+    caller.require_dashboard()?;
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.into();
+    if !app.installed {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    match app_state.database.issue_app_token(&app.id).await {
+        Ok(token) => Ok(Json(AppTokenResponse {
+            data: token,
+            links: AppResponseLinks {
+                self_link: format!("/api/apps/{}", app.id),
+            },
+        })),
+        Err(e) => {
+            tracing::error!("Failed to issue app token: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// The capabilities an app asks for and has been granted, for the install consent prompt
+pub async fn get_app_permissions(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<Json<AppPermissionsResponse>, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(permissions_response(app_doc.into())))
+}
+
+/// Grant an installed app the capabilities its current release declares, e.g. after an
+/// upgrade asked for more
+pub async fn grant_app_permissions(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+    Path(app_id): Path<String>,
+    JsonBody(req): JsonBody<ConsentRequest>,
+) -> Result<Json<AppPermissionsResponse>, StatusCode> {
+    // Consent comes from the user, never from an app
+    caller.require_dashboard()?;
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.clone().into();
+    if !app.installed {
+        // Installing is where consent is given
+        return Err(StatusCode::CONFLICT);
+    }
+    if !req.consent {
+        return Err(StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    let mut app_doc = app_doc;
+    let manifest = declared_manifest(&app);
+    if let Some(data_obj) = app_doc.data.as_object_mut() {
+        data_obj.insert("manifest".to_string(), serde_json::json!(manifest));
+        data_obj.insert("granted".to_string(), serde_json::json!(manifest));
+        data_obj.insert("granted_at".to_string(), serde_json::json!(chrono::Utc::now().to_rfc3339()));
+    }
+    let updated_doc = save_app(&app_state, app_doc).await?;
+    Ok(Json(permissions_response(updated_doc.into())))
+}
+
+/// Uninstall an app, keeping it in the catalog so it can be installed again.
+///
+/// With `?remove_data=true`, as for `delete_app`, its data collections are deleted too,
+/// after a snapshot that can bring them back.
+pub async fn uninstall_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+    Query(options): Query<RemoveAppDataOptions>,
+) -> Result<Json<AppUninstallResponse>, StatusCode> {
+    let app_doc = find_app(&app_state, &app_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.clone().into();
+    if !app.installed {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let meta = if options.remove_data {
+        remove_app_data(&app_state, &app).await?
+    } else {
+        AppUninstallMeta {
+            removed_collections: Vec::new(),
+            snapshot_id: None,
+        }
+    };
+
+    let updated_doc = set_installed(&app_state, app_doc, false).await?;
+    revoke_tokens(&app_state, &app.id).await?;
+    let response = app_response(updated_doc);
+    Ok(Json(AppUninstallResponse {
+        data: response.data,
+        meta,
+        links: response.links,
+    }))
+}
+
+/// Record the user's consent to what an app declares, refusing to go on without it when the
+/// app asks for anything it hasn't already been granted
+fn grant_declared_manifest(app: &App, mut app_doc: Document, consent: bool) -> Result<Document, StatusCode> {
+    let manifest = declared_manifest(app);
+    let already_granted = app
+        .granted
+        .as_ref()
+        .is_some_and(|granted| granted.covers(&manifest));
+    if !manifest.is_empty() && !already_granted && !consent {
+        return Err(StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    if let Some(data_obj) = app_doc.data.as_object_mut() {
+        data_obj.insert("manifest".to_string(), serde_json::json!(manifest));
+        data_obj.insert("granted".to_string(), serde_json::json!(manifest));
+        data_obj.insert("granted_at".to_string(), serde_json::json!(chrono::Utc::now().to_rfc3339()));
+    }
+    Ok(app_doc)
+}
+
+fn permissions_response(app: App) -> AppPermissionsResponse {
+    let manifest = declared_manifest(&app);
+    AppPermissionsResponse {
+        links: AppPermissionsLinks {
+            self_link: format!("/api/apps/{}/permissions", app.id),
+            app: format!("/api/apps/{}", app.id),
+            install: format!("/api/apps/{}/install", app.id),
+        },
+        data: AppPermissions {
+            app_id: app.id.clone(),
+            summary: manifest.summary(),
+            effective: effective_permissions(&app),
+            consent_required: app.consent_required,
+            granted: app.granted,
+            manifest,
+        },
+    }
+}
+
+/// Flag an installed app whose project has a newer release than the one it runs
+fn mark_update_available(app: &mut App, latest_release: &Document) {
+    let latest = release_ref(latest_release);
+    app.update_available = app.installed
+        && (app.project_version != Some(latest.project_version) || app.version != latest.version);
+    app.latest_version = Some(latest.version);
+}
+
+/// Check the collections an app claims, dropping blanks and duplicates
+fn validate_data_collections(collections: Vec<String>) -> Result<Vec<String>, StatusCode> {
+    let mut valid: Vec<String> = Vec::new();
+    for collection in collections {
+        let collection = collection.trim().to_string();
+        if collection.is_empty() || valid.contains(&collection) {
+            continue;
+        }
+        if SYSTEM_COLLECTIONS.contains(&collection.as_str()) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        valid.push(collection);
+    }
+    Ok(valid)
+}
+
+async fn remove_app_data(app_state: &AppState, app: &App) -> Result<AppUninstallMeta, StatusCode> {
+    let mut collections: Vec<String> = app
+        .data_collections
+        .iter()
+        .filter(|collection| !SYSTEM_COLLECTIONS.contains(&collection.as_str()))
+        .cloned()
+        .collect();
+
+    // Plus every collection in the app's own namespace
+    let namespace = namespaced_collection(&app.id, "");
+    let stored = app_state.database.list_collections().await.map_err(|e| {
+        tracing::error!("Failed to list collections: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    for collection in stored {
+        if collection.starts_with(&namespace) && !collections.contains(&collection) {
+            collections.push(collection);
+        }
+    }
+    if collections.is_empty() {
+        return Ok(AppUninstallMeta {
+            removed_collections: Vec::new(),
+            snapshot_id: None,
+        });
+    }
+
+    // Same safety net as a database reset
+    let snapshot = app_state
+        .database
+        .reset_with_snapshot("pre-uninstall", Some(&collections))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to remove app data: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(AppUninstallMeta {
+        removed_collections: collections,
+        snapshot_id: Some(snapshot.id),
+    })
+}
+
+async fn set_installed(
+    app_state: &AppState,
+    mut app_doc: Document,
+    installed: bool,
+) -> Result<Document, StatusCode> {
+    if let Some(data_obj) = app_doc.data.as_object_mut() {
+        data_obj.insert("installed".to_string(), serde_json::json!(installed));
+        if !installed {
+            // Nobody is running the app to be told about updates, and reinstalling it asks
+            // for consent again
+            data_obj.remove("update_notice");
+            data_obj.remove("granted");
+            data_obj.remove("granted_at");
+        }
+        data_obj.insert(
+            "updated_at".to_string(),
+            serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+    save_app(app_state, app_doc).await
+}
+
+/// Stop running instances of an app from using the host API
+async fn revoke_tokens(app_state: &AppState, app_id: &str) -> Result<(), StatusCode> {
+    app_state
+        .database
+        .revoke_app_tokens(app_id)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            tracing::error!("Failed to revoke app tokens: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+async fn save_app(app_state: &AppState, app_doc: Document) -> Result<Document, StatusCode> {
+    match app_state
+        .database
+        .update_document("apps", &app_doc.id, app_doc.data)
+        .await
+    {
+        Ok(Some(updated_doc)) => Ok(updated_doc),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to update app: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub(crate) async fn find_app(
+    app_state: &AppState,
+    app_id: &str,
+) -> Result<Option<Document>, StatusCode> {
+    let apps = app_state
+        .database
+        .list_documents("apps", Some(1000), Some(0))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list apps: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(apps
+        .documents
+        .into_iter()
+        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(app_id)))
+}
+
+fn app_response(app_doc: Document) -> AppResponse {
+    let app: App = app_doc.into();
+    AppResponse {
+        links: AppResponseLinks {
+            self_link: format!("/api/apps/{}", app.id),
+        },
+        data: app,
+    }
+}
@@ -0,0 +1,336 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::Json,
+};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use super::health::check_version_source;
+use super::projects::{find_project, find_project_versions};
+use super::releases::find_releases;
+use crate::archive::{build_archive, read_archive, ArchiveError};
+use crate::models::{
+    ImportProjectQuery, ProjectImport, ProjectImportLinks, ProjectImportResponse, DEFAULT_BRANCH,
+};
+use crate::AppState;
+
+const ARCHIVE_FORMAT: &str = "mini-server-project";
+const ARCHIVE_FORMAT_VERSION: i64 = 1;
+
+/// Export a project, all of its versions and its release records as a `.tar.gz`
+pub async fn export_project(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<([(header::HeaderName, String); 2], Vec<u8>), StatusCode> {
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let versions = find_project_versions(&app_state, &project_id).await?;
+    let releases = find_releases(&app_state, &project_id).await?;
+
+    let mut files = vec![(
+        "project.json".to_string(),
+        serde_json::to_vec_pretty(&project_doc.data).unwrap(),
+    )];
+    for version_doc in &versions {
+        let version_number = version_doc
+            .data
+            .get("version_number")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+        files.push((
+            format!("versions/{:06}.json", version_number),
+            serde_json::to_vec_pretty(&version_doc.data).unwrap(),
+        ));
+    }
+    let release_data: Vec<&serde_json::Value> = releases.iter().map(|doc| &doc.data).collect();
+    files.push((
+        "releases.json".to_string(),
+        serde_json::to_vec_pretty(&release_data).unwrap(),
+    ));
+
+    let manifest = serde_json::json!({
+        "format": ARCHIVE_FORMAT,
+        "format_version": ARCHIVE_FORMAT_VERSION,
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "project": {
+            "id": project_id,
+            "name": project_doc.data.get("name"),
+            "current_version": project_doc.data.get("current_version")
+        },
+        "counts": {
+            "versions": versions.len(),
+            "releases": releases.len()
+        }
+    });
+
+    let archive = build_archive(manifest, files).map_err(|e| {
+        tracing::error!("Failed to build project archive: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"project-{}.tar.gz\"", project_id),
+            ),
+        ],
+        archive,
+    ))
+}
+
+/// Import a project archive made by `export_project`.
+///
+/// In `new` mode the project, versions and releases are recreated under new ids in one
+/// transaction. In `merge` mode the archive's versions are appended to an existing project's
+/// branch, skipping any whose source code the project already has; releases aren't merged
+/// since their version numbers belong to the exported project.
+pub async fn import_project(
+    State(app_state): State<AppState>,
+    Query(query): Query<ImportProjectQuery>,
+    body: Bytes,
+) -> Result<Json<ProjectImportResponse>, StatusCode> {
+    let (manifest, files) = read_archive(&body).map_err(|e| {
+        tracing::warn!("Rejected project archive: {}", e);
+        match e {
+            ArchiveError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    })?;
+
+    if manifest.get("format").and_then(|v| v.as_str()) != Some(ARCHIVE_FORMAT)
+        || manifest.get("format_version").and_then(|v| v.as_i64()) != Some(ARCHIVE_FORMAT_VERSION)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let project_data: serde_json::Value = files
+        .get("project.json")
+        .and_then(|bytes| serde_json::from_slice(bytes).ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let source_project_id = project_data
+        .get("id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    let mut versions = Vec::new();
+    for (path, bytes) in &files {
+        if path.starts_with("versions/") {
+            let version: serde_json::Value =
+                serde_json::from_slice(bytes).map_err(|_| StatusCode::BAD_REQUEST)?;
+            versions.push(version);
+        }
+    }
+    versions.sort_by_key(|v| v.get("version_number").and_then(|n| n.as_i64()).unwrap_or(0));
+    let version_numbers = imported_version_numbers(&versions)?;
+
+    let releases: Vec<serde_json::Value> = match files.get("releases.json") {
+        Some(bytes) => serde_json::from_slice(bytes).map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => Vec::new(),
+    };
+
+    let mode = query.mode.unwrap_or_else(|| "new".to_string());
+    let import = match mode.as_str() {
+        "new" => {
+            check_history_refs(&project_data, &version_numbers)?;
+            import_as_new(&app_state, &source_project_id, project_data, versions, releases, &manifest)
+                .await?
+        }
+        "merge" => {
+            let into = query.into.ok_or(StatusCode::BAD_REQUEST)?;
+            let branch = query.branch.unwrap_or_else(|| DEFAULT_BRANCH.to_string());
+            merge_into(&app_state, &into, &branch, &source_project_id, versions).await?
+        }
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let response = ProjectImportResponse {
+        links: ProjectImportLinks {
+            project: format!("/api/projects/{}", import.project_id),
+            versions: format!("/api/projects/{}/versions", import.project_id),
+        },
+        data: import,
+    };
+    Ok(Json(response))
+}
+
+/// The version numbers of an archive's versions, refusing archives where one is missing or
+/// repeated
+fn imported_version_numbers(versions: &[serde_json::Value]) -> Result<HashSet<i64>, StatusCode> {
+    let mut version_numbers = HashSet::new();
+    for version in versions {
+        let version_number = version
+            .get("version_number")
+            .and_then(|v| v.as_i64())
+            .ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+        if !version_numbers.insert(version_number) {
+            tracing::warn!("Rejected project archive repeating version {}", version_number);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+    Ok(version_numbers)
+}
+
+/// Make sure the archived project's current version and branch heads are among the
+/// versions it carries
+fn check_history_refs(
+    project_data: &serde_json::Value,
+    version_numbers: &HashSet<i64>,
+) -> Result<(), StatusCode> {
+    let imported = |value: Option<&serde_json::Value>| {
+        value
+            .and_then(|v| v.as_i64())
+            .is_some_and(|version_number| version_numbers.contains(&version_number))
+    };
+
+    if !imported(project_data.get("current_version")) {
+        tracing::warn!("Rejected project archive whose current version isn't included");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    match project_data.get("branches") {
+        None | Some(serde_json::Value::Null) => {}
+        Some(serde_json::Value::Object(branches)) => {
+            if let Some((name, _)) = branches.iter().find(|(_, head)| !imported(Some(head))) {
+                tracing::warn!("Rejected project archive whose branch {} isn't included", name);
+                return Err(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+        }
+        Some(_) => return Err(StatusCode::UNPROCESSABLE_ENTITY),
+    }
+    Ok(())
+}
+
+async fn import_as_new(
+    app_state: &AppState,
+    source_project_id: &str,
+    mut project_data: serde_json::Value,
+    versions: Vec<serde_json::Value>,
+    releases: Vec<serde_json::Value>,
+    manifest: &serde_json::Value,
+) -> Result<ProjectImport, StatusCode> {
+    let project_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    if let Some(data_obj) = project_data.as_object_mut() {
+        data_obj.insert("id".to_string(), serde_json::json!(project_id));
+        data_obj.insert(
+            "imported_from".to_string(),
+            serde_json::json!({
+                "project_id": source_project_id,
+                "exported_at": manifest.get("exported_at")
+            }),
+        );
+        data_obj.insert("updated_at".to_string(), serde_json::json!(now));
+    }
+
+    let versions_imported = versions.len();
+    let releases_imported = releases.len();
+
+    let mut documents = vec![("projects".to_string(), project_data)];
+    for mut version in versions {
+        if let Some(data_obj) = version.as_object_mut() {
+            data_obj.insert("id".to_string(), serde_json::json!(Uuid::new_v4().to_string()));
+            data_obj.insert("project_id".to_string(), serde_json::json!(project_id));
+        }
+        documents.push(("project_versions".to_string(), version));
+    }
+    for mut release in releases {
+        if let Some(data_obj) = release.as_object_mut() {
+            data_obj.insert("id".to_string(), serde_json::json!(Uuid::new_v4().to_string()));
+            data_obj.insert("project_id".to_string(), serde_json::json!(project_id));
+            // Apps and earlier releases live on the exporting node
+            data_obj.remove("app_id");
+            data_obj.remove("previous_release_id");
+        }
+        documents.push(("releases".to_string(), release));
+    }
+
+    if let Err(e) = app_state.database.create_documents(documents).await {
+        tracing::error!("Failed to import project: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(ProjectImport {
+        project_id,
+        mode: "new".to_string(),
+        source_project_id: source_project_id.to_string(),
+        versions_imported,
+        versions_skipped: 0,
+        releases_imported,
+    })
+}
+
+async fn merge_into(
+    app_state: &AppState,
+    project_id: &str,
+    branch: &str,
+    source_project_id: &str,
+    versions: Vec<serde_json::Value>,
+) -> Result<ProjectImport, StatusCode> {
+    if find_project(app_state, project_id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut known_sources: HashSet<String> = find_project_versions(app_state, project_id)
+        .await?
+        .iter()
+        .filter_map(|doc| doc.data.get("source_code").and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+        .collect();
+
+    let mut new_versions = Vec::new();
+    let mut versions_skipped = 0;
+    for version in versions {
+        let source_code = version
+            .get("source_code")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        if !known_sources.insert(source_code) {
+            versions_skipped += 1;
+            continue;
+        }
+
+        let mut fields = serde_json::json!({
+            "prompt": version.get("prompt"),
+            "source_code": version.get("source_code"),
+            "model": version.get("model"),
+            "usage": version.get("usage"),
+            "imported_from": {
+                "project_id": source_project_id,
+                "version_number": version.get("version_number")
+            }
+        });
+        // Checks recorded in the archive come from another node, so they're run again here
+        check_version_source(&mut fields).await;
+        new_versions.push(fields);
+    }
+
+    // All of the archive's versions go in or none do
+    let versions_imported = match app_state
+        .database
+        .append_project_versions(project_id, branch, new_versions)
+        .await
+    {
+        Ok(Some(documents)) => documents.len(),
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to merge project archive: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(ProjectImport {
+        project_id: project_id.to_string(),
+        mode: "merge".to_string(),
+        source_project_id: source_project_id.to_string(),
+        versions_imported,
+        versions_skipped,
+        releases_imported: 0,
+    })
+}
@@ -0,0 +1,220 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Json as JsonBody,
+};
+use uuid::Uuid;
+
+use super::health::copy_version_checks;
+use super::projects::{append_version, find_project, find_project_version, version_response};
+use crate::models::{
+    project_branches, CreateBranchRequest, ForkProjectRequest, MergeBranchRequest, ProjectBranch,
+    ProjectBranchListLinks, ProjectBranchListResponse, ProjectResponse, ProjectResponseLinks,
+    ProjectVersionResponse, DEFAULT_BRANCH,
+};
+use crate::AppState;
+
+pub async fn fork_project(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+    JsonBody(req): JsonBody<ForkProjectRequest>,
+) -> Result<Json<ProjectResponse>, StatusCode> {
+    let source_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let version_number = req.version_number.unwrap_or_else(|| {
+        source_doc
+            .data
+            .get("current_version")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32
+    });
+    let version_doc = find_project_version(&app_state, &project_id, version_number)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let fork_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    let name = req.name.unwrap_or_else(|| {
+        let source_name = source_doc
+            .data
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Untitled Project");
+        format!("{} (fork)", source_name)
+    });
+
+    let fork_data = serde_json::json!({
+        "id": fork_id,
+        "name": name,
+        "description": source_doc.data.get("description"),
+        "icon": source_doc.data.get("icon"),
+        "status": "draft",
+        "current_version": 0,
+        "initial_prompt": source_doc.data.get("initial_prompt"),
+        "initial_model": source_doc.data.get("initial_model"),
+        "forked_from": {
+            "project_id": project_id,
+            "version_number": version_number
+        },
+        "created_at": now,
+        "updated_at": now
+    });
+
+    if let Err(e) = app_state
+        .database
+        .create_document("projects", fork_data)
+        .await
+    {
+        tracing::error!("Failed to create forked project: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // The forked version becomes version 1 of the new project
+    let mut fields = serde_json::json!({
+        "prompt": version_doc.data.get("prompt"),
+        "source_code": version_doc.data.get("source_code"),
+        "model": version_doc.data.get("model")
+    });
+    copy_version_checks(&version_doc, &mut fields).await;
+    append_version(&app_state, &fork_id, DEFAULT_BRANCH, fields).await?;
+
+    let fork_doc = find_project(&app_state, &fork_id)
+        .await?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let response = ProjectResponse {
+        data: fork_doc.into(),
+        links: ProjectResponseLinks {
+            self_link: format!("/api/projects/{}", fork_id),
+            versions: Some(format!("/api/projects/{}/versions", fork_id)),
+        },
+    };
+    Ok(Json(response))
+}
+
+pub async fn list_branches(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<ProjectBranchListResponse>, StatusCode> {
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(branch_list_response(&project_id, &project_doc.data)))
+}
+
+pub async fn create_branch(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+    JsonBody(req): JsonBody<CreateBranchRequest>,
+) -> Result<Json<ProjectBranchListResponse>, StatusCode> {
+    if !is_valid_branch_name(&req.name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let branches = project_branches(&project_doc.data);
+    if branches.contains_key(&req.name) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let from_version = match req.from_version {
+        Some(version_number) => version_number,
+        None => branches.get(DEFAULT_BRANCH).copied().unwrap_or(0),
+    };
+    if from_version > 0
+        && find_project_version(&app_state, &project_id, from_version)
+            .await?
+            .is_none()
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // Only the new branch is written; it loses to a branch of the same name added meanwhile
+    match app_state
+        .database
+        .add_project_branch(&project_id, &req.name, from_version)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::CONFLICT),
+        Err(e) => {
+            tracing::error!("Failed to create branch: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(branch_list_response(&project_id, &project_doc.data)))
+}
+
+pub async fn merge_branch(
+    State(app_state): State<AppState>,
+    Path((project_id, branch)): Path<(String, String)>,
+    JsonBody(req): JsonBody<MergeBranchRequest>,
+) -> Result<Json<ProjectVersionResponse>, StatusCode> {
+    let into = req.into.unwrap_or_else(|| DEFAULT_BRANCH.to_string());
+    if into == branch {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let branches = project_branches(&project_doc.data);
+    let (source_head, target_head) = match (branches.get(&branch), branches.get(&into)) {
+        (Some(source_head), Some(target_head)) => (*source_head, *target_head),
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+
+    // Merging means picking one of the two heads; no content-level merge is attempted
+    let chosen_head = req.head.unwrap_or(source_head);
+    if chosen_head != source_head && chosen_head != target_head {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let chosen_doc = find_project_version(&app_state, &project_id, chosen_head)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut fields = serde_json::json!({
+        "prompt": chosen_doc.data.get("prompt"),
+        "source_code": chosen_doc.data.get("source_code"),
+        "model": chosen_doc.data.get("model"),
+        "merged_from": source_head
+    });
+    copy_version_checks(&chosen_doc, &mut fields).await;
+    let version_doc = append_version(&app_state, &project_id, &into, fields).await?;
+
+    Ok(Json(version_response(&project_id, version_doc)))
+}
+
+fn branch_list_response(project_id: &str, project_data: &serde_json::Value) -> ProjectBranchListResponse {
+    ProjectBranchListResponse {
+        data: project_branches(project_data)
+            .into_iter()
+            .map(|(name, head_version)| ProjectBranch { name, head_version })
+            .collect(),
+        links: ProjectBranchListLinks {
+            self_link: format!("/api/projects/{}/branches", project_id),
+            project: format!("/api/projects/{}", project_id),
+        },
+    }
+}
+
+fn is_valid_branch_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Json as JsonBody,
+};
+
+use super::projects::find_project_version;
+use super::releases::find_releases;
+use crate::ai::generate_changelog;
+use crate::config::Config;
+use crate::diff::diff_sources;
+use crate::models::{
+    ChangelogRequest, ProjectVersionChangelog, ProjectVersionChangelogLinks,
+    ProjectVersionChangelogResponse, VersionChangelog,
+};
+use crate::AppState;
+
+/// Diffs longer than this are cut off before being sent to the model
+const MAX_CHANGELOG_DIFF_CHARS: usize = 60_000;
+
+/// Generate a changelog between two versions of a project.
+///
+/// The changelog is stored on the later of the two versions, replacing any earlier one, and
+/// copied onto every release of that version, which also take it as their notes unless
+/// they were given notes of their own.
+pub async fn generate_version_changelog(
+    State(app_state): State<AppState>,
+    Path((project_id, version_number, to_version)): Path<(String, i32, i32)>,
+    JsonBody(req): JsonBody<ChangelogRequest>,
+) -> Result<Json<ProjectVersionChangelogResponse>, StatusCode> {
+    if version_number == to_version {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (from_version, to_version) = if version_number < to_version {
+        (version_number, to_version)
+    } else {
+        (to_version, version_number)
+    };
+
+    let from_doc = find_project_version(&app_state, &project_id, from_version)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let to_doc = find_project_version(&app_state, &project_id, to_version)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let from_source = from_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or("");
+    let to_source = to_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or("");
+    let prompt = to_doc.data.get("prompt").and_then(|v| v.as_str()).unwrap_or("");
+    let model = req.model.unwrap_or_else(|| Config::DEFAULT_MODEL.to_string());
+
+    let text = if from_source == to_source {
+        // Nothing for the model to describe
+        "- No changes to the app.".to_string()
+    } else {
+        let diff = diff_sources(
+            from_source,
+            to_source,
+            &format!("v{}", from_version),
+            &format!("v{}", to_version),
+            3,
+        );
+        generate_changelog(
+            &app_state,
+            from_version,
+            to_version,
+            prompt,
+            truncate_diff(&diff.unified),
+            &model,
+        )
+        .await?
+    };
+
+    let changelog = VersionChangelog {
+        text,
+        from_version,
+        model,
+        generated_at: chrono::Utc::now(),
+    };
+
+    let mut version_data = to_doc.data.clone();
+    if let Some(data_obj) = version_data.as_object_mut() {
+        data_obj.insert("changelog".to_string(), serde_json::json!(changelog));
+    }
+    if let Err(e) = app_state
+        .database
+        .update_document("project_versions", &to_doc.id, version_data)
+        .await
+    {
+        tracing::error!("Failed to save version changelog: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let mut release_ids = Vec::new();
+    for release_doc in find_releases(&app_state, &project_id).await? {
+        if release_doc.data.get("project_version").and_then(|v| v.as_i64()) != Some(to_version as i64) {
+            continue;
+        }
+
+        let mut release_data = release_doc.data.clone();
+        if let Some(data_obj) = release_data.as_object_mut() {
+            let notes = data_obj.get("notes").and_then(|v| v.as_str()).unwrap_or("");
+            let previous_changelog = data_obj.get("changelog").and_then(|v| v.as_str());
+            if notes.trim().is_empty() || previous_changelog == Some(notes) {
+                data_obj.insert("notes".to_string(), serde_json::json!(changelog.text));
+            }
+            data_obj.insert("changelog".to_string(), serde_json::json!(changelog.text));
+        }
+        if let Err(e) = app_state
+            .database
+            .update_document("releases", &release_doc.id, release_data)
+            .await
+        {
+            tracing::error!("Failed to save release changelog: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        if let Some(release_id) = release_doc.data.get("id").and_then(|v| v.as_str()) {
+            release_ids.push(release_id.to_string());
+        }
+    }
+
+    let response = ProjectVersionChangelogResponse {
+        data: ProjectVersionChangelog {
+            project_id: project_id.clone(),
+            to_version,
+            changelog,
+            release_ids,
+        },
+        links: ProjectVersionChangelogLinks {
+            self_link: format!(
+                "/api/projects/{}/versions/{}/changelog/{}",
+                project_id, from_version, to_version
+            ),
+            version: format!("/api/projects/{}/versions/{}", project_id, to_version),
+            diff: format!(
+                "/api/projects/{}/versions/{}/diff/{}",
+                project_id, from_version, to_version
+            ),
+        },
+    };
+    Ok(Json(response))
+}
+
+fn truncate_diff(diff: &str) -> &str {
+    if diff.len() <= MAX_CHANGELOG_DIFF_CHARS {
+        return diff;
+    }
+    let mut end = MAX_CHANGELOG_DIFF_CHARS;
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    &diff[..end]
+}
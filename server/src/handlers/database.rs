@@ -5,24 +5,35 @@ use axum::{
     Json as JsonBody,
 };
 
-use crate::models::{CreateDocumentRequest, ListQuery, QueryRequest, UpdateDocumentRequest};
+use crate::capabilities::AppCaller;
+use crate::models::{
+    CollectionAccess, CreateDocumentRequest, Document, ListQuery, PrepareResetRequest, QueryRequest, ResetDatabaseRequest,
+    SchemaQuery, UpdateDocumentRequest,
+};
+use crate::schema::infer_schema;
 use crate::AppState;
 
 pub async fn create_document(
     State(app_state): State<AppState>,
+    caller: AppCaller,
     Path(collection): Path<String>,
     JsonBody(req): JsonBody<CreateDocumentRequest>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
+    let stored = caller.authorize(&collection, CollectionAccess::ReadWrite)?;
+    caller
+        .check_storage(&app_state.database, &stored, None, &req.data)
+        .await?;
+
     match app_state
         .database
-        .create_document(&collection, req.data)
+        .create_document(&stored, req.data)
         .await
     {
         Ok(document) => Ok(Json(serde_json::json!({
-            "data": document,
             "links": {
                 "self": format!("/api/db/{}/{}", collection, document.id)
-            }
+            },
+            "data": as_named(document, &collection),
         }))),
         Err(e) => {
             tracing::error!("Failed to create document: {}", e);
@@ -33,11 +44,14 @@ pub async fn create_document(
 
 pub async fn get_document(
     State(app_state): State<AppState>,
+    caller: AppCaller,
     Path((collection, id)): Path<(String, String)>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
-    match app_state.database.get_document(&collection, &id).await {
+    let stored = caller.authorize(&collection, CollectionAccess::Read)?;
+
+    match app_state.database.get_document(&stored, &id).await {
         Ok(Some(document)) => Ok(Json(serde_json::json!({
-            "data": document,
+            "data": as_named(document, &collection),
             "links": {
                 "self": format!("/api/db/{}/{}", collection, id),
                 "collection": format!("/api/db/{}", collection)
@@ -53,16 +67,22 @@ pub async fn get_document(
 
 pub async fn update_document(
     State(app_state): State<AppState>,
+    caller: AppCaller,
     Path((collection, id)): Path<(String, String)>,
     JsonBody(req): JsonBody<UpdateDocumentRequest>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
+    let stored = caller.authorize(&collection, CollectionAccess::ReadWrite)?;
+    caller
+        .check_storage(&app_state.database, &stored, Some(&id), &req.data)
+        .await?;
+
     match app_state
         .database
-        .update_document(&collection, &id, req.data)
+        .update_document(&stored, &id, req.data)
         .await
     {
         Ok(Some(document)) => Ok(Json(serde_json::json!({
-            "data": document,
+            "data": as_named(document, &collection),
             "links": {
                 "self": format!("/api/db/{}/{}", collection, id),
                 "collection": format!("/api/db/{}", collection)
@@ -78,9 +98,12 @@ pub async fn update_document(
 
 pub async fn delete_document(
     State(app_state): State<AppState>,
+    caller: AppCaller,
     Path((collection, id)): Path<(String, String)>,
 ) -> Result<StatusCode, StatusCode> {
-    match app_state.database.delete_document(&collection, &id).await {
+    let stored = caller.authorize(&collection, CollectionAccess::ReadWrite)?;
+
+    match app_state.database.delete_document(&stored, &id).await {
         Ok(true) => Ok(StatusCode::NO_CONTENT),
         Ok(false) => Err(StatusCode::NOT_FOUND),
         Err(e) => {
@@ -92,19 +115,25 @@ pub async fn delete_document(
 
 pub async fn list_documents(
     State(app_state): State<AppState>,
+    caller: AppCaller,
     Path(collection): Path<String>,
     Query(query): Query<ListQuery>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
+    let stored = caller.authorize(&collection, CollectionAccess::Read)?;
     let limit = query.limit.unwrap_or(100);
     let offset = query.offset.unwrap_or(0);
 
     match app_state
         .database
-        .list_documents(&collection, Some(limit), Some(offset))
+        .list_documents(&stored, Some(limit), Some(offset))
         .await
     {
         Ok(result) => Ok(Json(serde_json::json!({
-            "data": result.documents,
+            "data": result
+                .documents
+                .into_iter()
+                .map(|document| as_named(document, &collection))
+                .collect::<Vec<_>>(),
             "meta": {
                 "count": result.count,
                 "limit": limit,
@@ -122,10 +151,51 @@ pub async fn list_documents(
     }
 }
 
-pub async fn list_collections(State(app_state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
+pub async fn get_collection_schema(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+    Path(collection): Path<String>,
+    Query(query): Query<SchemaQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let stored = caller.authorize(&collection, CollectionAccess::Read)?;
+    // A negative limit would mean "no limit" to SQLite, so always sample at least one
+    let sample = query.sample.unwrap_or(100).max(1);
+
+    match app_state
+        .database
+        .list_documents(&stored, Some(sample), Some(0))
+        .await
+    {
+        Ok(result) => {
+            let samples: Vec<serde_json::Value> =
+                result.documents.into_iter().map(|doc| doc.data).collect();
+
+            Ok(Json(serde_json::json!({
+                "data": infer_schema(&collection, &samples),
+                "meta": {
+                    "sampled": samples.len(),
+                    "count": result.count
+                },
+                "links": {
+                    "self": format!("/api/db/{}/_schema", collection),
+                    "collection": format!("/api/db/{}", collection)
+                }
+            })))
+        }
+        Err(e) => {
+            tracing::error!("Failed to infer collection schema: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn list_collections(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+) -> Result<Json<serde_json::Value>, StatusCode> {
     match app_state.database.list_collections().await {
         Ok(collections) => Ok(Json(serde_json::json!({
-            "data": collections,
+            "data": caller.visible_collections(collections),
             "links": {
                 "self": "/api/db",
                 "collections": "/api/db"
@@ -140,8 +210,12 @@ pub async fn list_collections(State(app_state): State<AppState>) -> Result<Json<
 
 pub async fn execute_query(
     State(app_state): State<AppState>,
+    caller: AppCaller,
     JsonBody(req): JsonBody<QueryRequest>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
+    // Raw SQL can read any collection
+    caller.require_dashboard()?;
+
     match app_state.database.execute_raw_query(&req.query).await {
         Ok(result) => Ok(Json(serde_json::json!({
             "data": result,
@@ -160,13 +234,74 @@ pub async fn execute_query(
     }
 }
 
-pub async fn reset_database(State(app_state): State<AppState>) -> Result<Json<serde_json::Value>, StatusCode> {
-    match app_state.database.reset_database().await {
-        Ok(_) => Ok(Json(serde_json::json!({
+pub async fn prepare_reset(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+    JsonBody(req): JsonBody<PrepareResetRequest>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    // Apps may only clear collections they can write, and never the whole database
+    let collections = match caller.0 {
+        Some(_) => Some(
+            req.collections
+                .as_deref()
+                .ok_or(StatusCode::FORBIDDEN)?
+                .iter()
+                .map(|collection| caller.authorize(collection, CollectionAccess::ReadWrite))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => req.collections,
+    };
+
+    match app_state.database.prepare_reset(collections).await {
+        Ok(reset_token) => Ok(Json(serde_json::json!({
+            "data": reset_token,
+            "links": {
+                "self": "/api/db/reset/prepare",
+                "reset": "/api/db/reset"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to prepare database reset: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn reset_database(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+    JsonBody(req): JsonBody<ResetDatabaseRequest>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let collections = match app_state.database.consume_reset_token(&req.token).await {
+        Ok(Some(collections)) => collections,
+        Ok(None) => return Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            tracing::error!("Failed to verify reset token: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    // The token may have been prepared by someone else, so apps are held to the same
+    // limits as when preparing one; it names the collections as they are stored
+    if caller.0.is_some() {
+        for collection in collections.as_deref().ok_or(StatusCode::FORBIDDEN)? {
+            caller.authorize_stored(collection, CollectionAccess::ReadWrite)?;
+        }
+    }
+
+    // Always keep a way back before destroying anything
+    match app_state
+        .database
+        .reset_with_snapshot("pre-reset", collections.as_deref())
+        .await
+    {
+        Ok(snapshot) => Ok(Json(serde_json::json!({
             "message": "Database reset successfully",
+            "data": snapshot,
             "links": {
                 "self": "/api/db/reset",
-                "collections": "/api/db"
+                "collections": "/api/db",
+                "restore": format!("/api/snapshots/{}/restore", snapshot.id)
             }
         }))),
         Err(e) => {
@@ -174,4 +309,55 @@ pub async fn reset_database(State(app_state): State<AppState>) -> Result<Json<se
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
-}
\ No newline at end of file
+}
+
+pub async fn list_snapshots(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    caller.require_dashboard()?;
+
+    match app_state.database.list_snapshots().await {
+        Ok(snapshots) => Ok(Json(serde_json::json!({
+            "data": snapshots,
+            "links": {
+                "self": "/api/snapshots"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to list snapshots: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn restore_snapshot(
+    State(app_state): State<AppState>,
+    caller: AppCaller,
+    Path(snapshot_id): Path<String>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    caller.require_dashboard()?;
+
+    match app_state.database.restore_snapshot(&snapshot_id).await {
+        Ok(Some(snapshot)) => Ok(Json(serde_json::json!({
+            "message": "Snapshot restored successfully",
+            "data": snapshot,
+            "links": {
+                "self": format!("/api/snapshots/{}/restore", snapshot_id),
+                "collections": "/api/db"
+            }
+        }))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to restore snapshot: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// A document as the caller sees it, under the collection name it asked for rather than the
+/// app namespace it is stored in
+fn as_named(mut document: Document, collection: &str) -> Document {
+    document.collection = collection.to_string();
+    document
+}
@@ -0,0 +1,184 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    Json as JsonBody,
+};
+use futures::stream::Stream;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::health::check_version_source;
+use super::projects::{append_version, find_project, find_project_version, version_response};
+use crate::ai::{
+    generation_request_body, modification_request_body, parse_streaming_response,
+    send_streaming_request, token_event, usage_event, StreamChunk,
+};
+use crate::config::Config;
+use crate::models::{project_branches, GenerateVersionRequest, DEFAULT_BRANCH};
+use crate::AppState;
+
+/// Generate the next version of a project, streaming tokens like `/generate`.
+///
+/// The generation runs in its own task and the version is stored once the model
+/// finishes, so closing the stream early doesn't lose the result.
+pub async fn generate_project_version(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+    JsonBody(req): JsonBody<GenerateVersionRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    // Validate API key early
+    Config::anthropic_api_key().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let project_doc = find_project(&app_state, &project_id)
+        .await
+        .map_err(|status| (status, "Failed to load project".to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Project not found".to_string()))?;
+
+    let branch = req.branch.unwrap_or_else(|| DEFAULT_BRANCH.to_string());
+    let head = *project_branches(&project_doc.data)
+        .get(&branch)
+        .ok_or((StatusCode::NOT_FOUND, "Branch not found".to_string()))?;
+
+    // Modify the branch head if there is one, otherwise start from scratch
+    let existing_code = if head > 0 {
+        find_project_version(&app_state, &project_id, head)
+            .await
+            .map_err(|status| (status, "Failed to load project version".to_string()))?
+            .and_then(|doc| {
+                doc.data
+                    .get("source_code")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            })
+    } else {
+        None
+    };
+
+    let model = req
+        .model
+        .unwrap_or_else(|| Config::DEFAULT_MODEL.to_string());
+    let body = match &existing_code {
+        Some(code) => modification_request_body(&model, code, &req.prompt),
+        None => generation_request_body(&model, &req.prompt),
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(run_generation(
+        app_state,
+        project_id,
+        branch,
+        req.prompt,
+        model,
+        body,
+        tx,
+    ));
+
+    let stream = async_stream::stream! {
+        while let Some(event) = rx.recv().await {
+            yield Ok(event);
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    ))
+}
+
+async fn run_generation(
+    app_state: AppState,
+    project_id: String,
+    branch: String,
+    prompt: String,
+    model: String,
+    body: serde_json::Value,
+    tx: mpsc::UnboundedSender<Event>,
+) {
+    // Sending fails once the client has gone away; the generation carries on regardless
+    let send = |event: Event| {
+        let _ = tx.send(event);
+    };
+
+    send(Event::default().data("Starting generation..."));
+    send(Event::default().data("Sending request to Anthropic API..."));
+
+    let response = match send_streaming_request(&app_state, &body).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Request failed: {}", e);
+            send(Event::default().data(format!("Error: {}", e)));
+            return;
+        }
+    };
+
+    send(Event::default().data("Streaming response from Anthropic API..."));
+
+    use futures::StreamExt;
+    let chunks = parse_streaming_response(response);
+    let mut chunks = std::pin::Pin::from(Box::new(chunks));
+
+    let mut source_code = String::new();
+    let mut usage = None;
+    let mut completed = false;
+
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            StreamChunk::Started => {
+                send(Event::default().data("Starting message generation..."));
+            }
+            StreamChunk::Token(text) => {
+                send(token_event(&text));
+                source_code.push_str(&text);
+            }
+            StreamChunk::Stopped(stop_usage) => {
+                if let Some(stop_usage) = &stop_usage {
+                    send(usage_event(stop_usage));
+                }
+                usage = stop_usage;
+                completed = true;
+                break;
+            }
+            StreamChunk::Done => {
+                completed = true;
+                break;
+            }
+            StreamChunk::Failed(e) => {
+                send(Event::default().data(format!("Error: {}", e)));
+                return;
+            }
+        }
+    }
+
+    if !completed || source_code.trim().is_empty() {
+        tracing::error!("Generation for project {} ended without output", project_id);
+        send(Event::default().data("Error: Generation ended early, no version was saved"));
+        return;
+    }
+
+    let mut fields = serde_json::json!({
+        "prompt": prompt,
+        "source_code": source_code,
+        "model": model,
+        "usage": usage
+    });
+    check_version_source(&mut fields).await;
+
+    match append_version(&app_state, &project_id, &branch, fields).await {
+        Ok(version_doc) => {
+            let response = version_response(&project_id, version_doc);
+            send(
+                Event::default()
+                    .event("version")
+                    .data(serde_json::to_string(&response).unwrap_or_default()),
+            );
+            send(Event::default().data("Generation complete!"));
+        }
+        Err(status) => {
+            tracing::error!("Failed to save generated version for project {}: {}", project_id, status);
+            send(Event::default().data(format!("Error: Failed to save version ({})", status)));
+        }
+    }
+}
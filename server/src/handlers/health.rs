@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde_json::Value;
+
+use super::projects::{find_project_version, version_response};
+use super::sources::SourceRejection;
+use crate::health::smoke_test;
+use crate::models::{Document, ProjectVersionResponse};
+use crate::validation::validate_source;
+use crate::AppState;
+
+/// Validate the source of a new version's `fields` and store the diagnostics with it.
+///
+/// Broken source is still saved, so it can be fixed in a later version, but the diagnostics
+/// keep it from being released. Only source that passes is worth running headlessly, so
+/// only then is it smoke tested and its `health` stored too.
+pub(crate) async fn check_version_source(fields: &mut Value) {
+    let source_code = match fields.get("source_code").and_then(|v| v.as_str()) {
+        Some(source_code) => source_code.to_string(),
+        None => return,
+    };
+    let validation = validate_source(&source_code);
+    let health = match validation.has_errors() {
+        true => None,
+        false => Some(smoke_test(source_code).await),
+    };
+    if let Some(data_obj) = fields.as_object_mut() {
+        data_obj.insert("validation".to_string(), serde_json::json!(validation));
+        if let Some(health) = health {
+            data_obj.insert("health".to_string(), serde_json::json!(health));
+        }
+    }
+}
+
+/// Give a version whose source is copied from `from` (a revert, fork or branch merge) the
+/// same checks, running them afresh if `from` was saved before they existed
+pub(crate) async fn copy_version_checks(from: &Document, fields: &mut Value) {
+    let validation = match from.data.get("validation") {
+        Some(validation) if !validation.is_null() => validation.clone(),
+        _ => return check_version_source(fields).await,
+    };
+    if let Some(data_obj) = fields.as_object_mut() {
+        data_obj.insert("validation".to_string(), validation);
+        if let Some(health) = from.data.get("health").filter(|health| !health.is_null()) {
+            data_obj.insert("health".to_string(), health.clone());
+        }
+    }
+}
+
+/// Run a version's source headlessly again and store the result as its `health`.
+///
+/// Versions are smoke tested when they are saved; this refreshes the result, e.g. for
+/// versions saved before the check existed. As when saving, source that fails validation
+/// isn't run: the request fails with `422` and the diagnostics.
+pub async fn smoke_test_version(
+    State(app_state): State<AppState>,
+    Path((project_id, version_number)): Path<(String, i32)>,
+) -> Result<Json<ProjectVersionResponse>, SourceRejection> {
+    let mut version_doc = find_project_version(&app_state, &project_id, version_number)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let source_code = version_doc
+        .data
+        .get("source_code")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    let validation = validate_source(&source_code);
+    if validation.has_errors() {
+        return Err(SourceRejection::Invalid(validation));
+    }
+
+    let health = smoke_test(source_code).await;
+    if let Some(data_obj) = version_doc.data.as_object_mut() {
+        data_obj.insert("validation".to_string(), serde_json::json!(validation));
+        data_obj.insert("health".to_string(), serde_json::json!(health));
+    }
+    match app_state
+        .database
+        .update_document("project_versions", &version_doc.id, version_doc.data)
+        .await
+    {
+        Ok(Some(updated_doc)) => Ok(Json(version_response(&project_id, updated_doc))),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into()),
+        Err(e) => {
+            tracing::error!("Failed to save version health: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into())
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+
+use crate::models::IntegrityQuery;
+use crate::AppState;
+
+pub async fn get_database_stats(
+    State(app_state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match app_state.database.database_stats().await {
+        Ok(stats) => Ok(Json(serde_json::json!({
+            "data": stats,
+            "links": {
+                "self": "/api/admin/db/stats"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to read database stats: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn vacuum_database(
+    State(app_state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match app_state.database.vacuum().await {
+        Ok(report) => Ok(Json(serde_json::json!({
+            "data": report,
+            "links": {
+                "self": "/api/admin/db/vacuum",
+                "stats": "/api/admin/db/stats"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to vacuum database: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn collect_sources(
+    State(app_state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match app_state.database.collect_sources().await {
+        Ok(report) => Ok(Json(serde_json::json!({
+            "data": report,
+            "links": {
+                "self": "/api/admin/db/collect-sources",
+                "stats": "/api/admin/db/stats"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to collect unreferenced sources: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn analyze_database(
+    State(app_state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match app_state.database.analyze().await {
+        Ok(report) => Ok(Json(serde_json::json!({
+            "data": report,
+            "links": {
+                "self": "/api/admin/db/analyze",
+                "stats": "/api/admin/db/stats"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to analyze database: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn check_database_integrity(
+    State(app_state): State<AppState>,
+    Query(query): Query<IntegrityQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match app_state
+        .database
+        .integrity_check(query.quick.unwrap_or(false))
+        .await
+    {
+        Ok(report) => Ok(Json(serde_json::json!({
+            "data": report,
+            "links": {
+                "self": "/api/admin/db/integrity"
+            }
+        }))),
+        Err(e) => {
+            tracing::error!("Failed to check database integrity: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -1,8 +1,19 @@
 pub mod apps;
+pub mod archive;
+pub mod branches;
+pub mod changelogs;
 pub mod dashboard;
 pub mod database;
 pub mod docs;
+pub mod generation;
+pub mod health;
+pub mod maintenance;
 pub mod projects;
+pub mod releases;
+pub mod retention;
+pub mod runtime;
+pub mod sources;
+pub mod templates;
 
 // Re-export database handlers
 pub use database::*;
@@ -10,10 +21,43 @@ pub use database::*;
 // Re-export project handlers
 pub use projects::*;
 
+// Re-export changelog handlers
+pub use changelogs::*;
+
+// Re-export generation handlers
+pub use generation::*;
+
+// Re-export version health handlers
+pub use health::*;
+
+// Re-export project import/export handlers
+pub use archive::*;
+
+// Re-export template handlers
+pub use templates::*;
+
+// Re-export release handlers
+pub use releases::*;
+
+// Re-export retention handlers
+pub use retention::*;
+
+// Re-export branch and fork handlers
+pub use branches::*;
+
 // Re-export app handlers
 pub use apps::*;
 
+// Re-export standalone app page handlers
+pub use runtime::*;
+
+// Re-export source handlers
+pub use sources::*;
+
 // Re-export dashboard handlers
 pub use dashboard::*;
 
+// Re-export maintenance handlers
+pub use maintenance::*;
+
 // docs handlers deprecated - using aide for OpenAPI generation
\ No newline at end of file
@@ -1,9 +1,15 @@
 use crate::ai::generate_metadata_from_prompt;
+use crate::diff::diff_sources;
+use crate::listing::{parse_date_param, DocumentFilter, DocumentSort};
 use crate::models::{
-    ListQuery, ProjectResponse, ProjectResponseLinks, ProjectListResponse, ProjectListMeta, ProjectListLinks,
+    DEFAULT_BRANCH, ListProjectsQuery, ProjectResponse, ProjectStatus, ProjectStatusChange, ProjectResponseLinks, ProjectListResponse, ProjectListMeta, ProjectListLinks,
     ProjectVersionResponse, ProjectVersionResponseLinks, ProjectVersionListResponse, ProjectVersionListMeta, ProjectVersionListLinks,
-    AppResponse, AppResponseLinks
+    App, AppManifest, AppResponse, AppResponseLinks, DiffQuery, Document, ProjectVersion, ProjectVersionDiff, ProjectVersionDiffLinks,
+    ProjectVersionDiffResponse,
 };
+use super::health::{check_version_source, copy_version_checks};
+use super::releases::{publish_release, ReleaseOptions};
+use super::sources::SourceRejection;
 use crate::AppState;
 use axum::{
     extract::{Path, Query, State},
@@ -26,6 +32,8 @@ pub struct CreateVersionRequest {
     pub prompt: String,
     pub source_code: String,
     pub model: Option<String>,
+    /// Branch to append to (default "main")
+    pub branch: Option<String>,
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -33,7 +41,7 @@ pub struct UpdateProjectRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub icon: Option<String>,
-    pub status: Option<String>,
+    pub status: Option<ProjectStatus>,
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -46,6 +54,12 @@ pub struct ConvertToAppRequest {
 pub struct ReleaseVersionRequest {
     pub version_number: i32,
     pub price: Option<f64>,
+    /// Semantic version for the release; defaults to a patch bump of the latest release
+    pub version: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub notes: Option<String>,
+    /// Capabilities the released app needs; worked out from its source when omitted
+    pub manifest: Option<AppManifest>,
 }
 
 pub async fn create_project(
@@ -97,14 +111,21 @@ pub async fn create_project(
 
 pub async fn list_projects(
     State(app_state): State<AppState>,
-    Query(query): Query<ListQuery>,
+    Query(query): Query<ListProjectsQuery>,
 ) -> Result<Json<ProjectListResponse>, StatusCode> {
     let limit = query.limit.unwrap_or(100);
     let offset = query.offset.unwrap_or(0);
+    let (mut filter, sort) = project_listing(&query)?;
+
+    // Archived projects only show up when asked for
+    filter.status = query.status.map(|status| status.as_str().to_string());
+    if query.status.is_none() && !query.include_archived.unwrap_or(false) {
+        filter.exclude_statuses.push(ProjectStatus::Archived.as_str().to_string());
+    }
 
     match app_state
         .database
-        .list_documents("projects", Some(limit), Some(offset))
+        .list_filtered_documents("projects", &filter, &sort, Some(limit), Some(offset))
         .await
     {
         Ok(result) => {
@@ -133,28 +154,9 @@ pub async fn get_project(
     State(app_state): State<AppState>,
     Path(project_id): Path<String>,
 ) -> Result<Json<ProjectResponse>, StatusCode> {
-    // Find project by data.id field
-    let projects_result = app_state
-        .database
-        .list_documents("projects", Some(1000), Some(0))
-        .await;
-    let projects = match projects_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let project_doc = projects
-        .documents
-        .into_iter()
-        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&project_id));
-
-    let project_doc = match project_doc {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
     // Get the current version to check if source_code needs generation
     let current_version = project_doc
@@ -163,25 +165,7 @@ pub async fn get_project(
         .and_then(|v| v.as_i64())
         .unwrap_or(0) as i32;
 
-    // Find the current version document
-    let versions_result = app_state
-        .database
-        .list_documents("project_versions", Some(1000), Some(0))
-        .await;
-    let versions = match versions_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list project versions: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    // Get all versions for this project first
-    let all_versions: Vec<_> = versions
-        .documents
-        .into_iter()
-        .filter(|doc| doc.data.get("project_id").and_then(|v| v.as_str()) == Some(&project_id))
-        .collect();
+    let all_versions = find_project_versions(&app_state, &project_id).await?;
 
     // Handle case where there are no versions (current_version = 0)
     if current_version == 0 {
@@ -234,55 +218,65 @@ pub async fn update_project(
     Path(project_id): Path<String>,
     JsonBody(req): JsonBody<UpdateProjectRequest>,
 ) -> Result<Json<ProjectResponse>, StatusCode> {
-    // Find the project by its data.id field
-    let projects_result = app_state
-        .database
-        .list_documents("projects", Some(1000), Some(0))
-        .await;
-    let projects = match projects_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    let project_document = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let now = chrono::Utc::now();
+    let status_change = match req.status {
+        Some(next) => {
+            let current = ProjectStatus::from_data(&project_document.data);
+            if next == current {
+                None
+            } else if !current.can_transition_to(next) {
+                return Err(StatusCode::CONFLICT);
+            } else {
+                // Only projects with something to show can be published
+                let current_version = project_document
+                    .data
+                    .get("current_version")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or(0);
+                if next == ProjectStatus::Published && current_version == 0 {
+                    return Err(StatusCode::CONFLICT);
+                }
+                Some(ProjectStatusChange {
+                    from: current,
+                    to: next,
+                    changed_at: now,
+                })
+            }
         }
+        None => None,
     };
 
-    let project_document = projects
-        .documents
-        .into_iter()
-        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&project_id));
-
-    let mut project_document = match project_document {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
-
-    // Update fields if provided
-    if let Some(data_obj) = project_document.data.as_object_mut() {
-        if let Some(name) = req.name {
-            data_obj.insert("name".to_string(), serde_json::Value::String(name));
-        }
-        if let Some(description) = req.description {
-            data_obj.insert(
-                "description".to_string(),
-                serde_json::Value::String(description),
-            );
-        }
-        if let Some(icon) = req.icon {
-            data_obj.insert("icon".to_string(), serde_json::Value::String(icon));
-        }
-        if let Some(status) = req.status {
-            data_obj.insert("status".to_string(), serde_json::Value::String(status));
-        }
-        data_obj.insert(
-            "updated_at".to_string(),
-            serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
-        );
+    // Only the fields provided are written, so versions saved meanwhile aren't undone
+    let mut fields = Vec::new();
+    if let Some(name) = req.name {
+        fields.push(("name", Some(serde_json::Value::String(name))));
     }
+    if let Some(description) = req.description {
+        fields.push(("description", Some(serde_json::Value::String(description))));
+    }
+    if let Some(icon) = req.icon {
+        fields.push(("icon", Some(serde_json::Value::String(icon))));
+    }
+    if let Some(change) = status_change {
+        let mut history = project_document
+            .data
+            .get("status_history")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        history.push(serde_json::json!(change));
+        fields.push(("status", Some(serde_json::json!(change.to))));
+        fields.push(("status_history", Some(serde_json::Value::Array(history))));
+    }
+    fields.push(("updated_at", Some(serde_json::Value::String(now.to_rfc3339()))));
 
     match app_state
         .database
-        .update_document("projects", &project_document.id, project_document.data)
+        .patch_document("projects", &project_document.id, fields)
         .await
     {
         Ok(Some(updated_document)) => {
@@ -307,57 +301,8 @@ pub async fn delete_project(
     State(app_state): State<AppState>,
     Path(project_id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
-    // Find and delete the project
-    let projects_result = app_state
-        .database
-        .list_documents("projects", Some(1000), Some(0))
-        .await;
-    let projects = match projects_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let project_document = projects
-        .documents
-        .into_iter()
-        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&project_id));
-
-    let project_document = match project_document {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
-
-    match app_state
-        .database
-        .delete_document("projects", &project_document.id)
-        .await
-    {
-        Ok(true) => {
-            // Also delete all versions for this project
-            // Note: In a production system, you might want to do this in a transaction
-            let versions_result = app_state
-                .database
-                .list_documents("project_versions", Some(1000), Some(0))
-                .await;
-            if let Ok(versions) = versions_result {
-                for version_doc in versions.documents {
-                    if let Some(version_project_id) =
-                        version_doc.data.get("project_id").and_then(|v| v.as_str())
-                    {
-                        if version_project_id == project_id {
-                            let _ = app_state
-                                .database
-                                .delete_document("project_versions", &version_doc.id)
-                                .await;
-                        }
-                    }
-                }
-            }
-            Ok(StatusCode::NO_CONTENT)
-        }
+    match app_state.database.delete_project(&project_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
         Ok(false) => Err(StatusCode::NOT_FOUND),
         Err(e) => {
             tracing::error!("Failed to delete project: {}", e);
@@ -371,78 +316,80 @@ pub async fn create_version(
     Path(project_id): Path<String>,
     JsonBody(req): JsonBody<CreateVersionRequest>,
 ) -> Result<Json<ProjectVersionResponse>, StatusCode> {
-    // Get current project to determine next version number
-    let projects_result = app_state
-        .database
-        .list_documents("projects", Some(1000), Some(0))
-        .await;
-    let projects = match projects_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+    let mut fields = serde_json::json!({
+        "prompt": req.prompt,
+        "source_code": req.source_code,
+        "model": req.model
+    });
+    check_version_source(&mut fields).await;
+    let version_doc = append_version(
+        &app_state,
+        &project_id,
+        req.branch.as_deref().unwrap_or(DEFAULT_BRANCH),
+        fields,
+    )
+    .await?;
+
+    Ok(Json(version_response(&project_id, version_doc)))
+}
 
-    let project_document = projects
-        .documents
-        .into_iter()
-        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&project_id));
+pub async fn get_version(
+    State(app_state): State<AppState>,
+    Path((project_id, version_number)): Path<(String, i32)>,
+) -> Result<Json<ProjectVersionResponse>, StatusCode> {
+    match find_project_version(&app_state, &project_id, version_number).await? {
+        Some(version_doc) => Ok(Json(version_response(&project_id, version_doc))),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
 
-    let mut project_document = match project_document {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
+pub async fn revert_version(
+    State(app_state): State<AppState>,
+    Path((project_id, version_number)): Path<(String, i32)>,
+) -> Result<Json<ProjectVersionResponse>, StatusCode> {
+    let target_doc = find_project_version(&app_state, &project_id, version_number)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
-    let current_version = project_document
+    // Reverting never rewrites history: the old version is copied forward as a new one
+    let branch = target_doc
         .data
-        .get("current_version")
-        .and_then(|v| v.as_i64())
-        .unwrap_or(0) as i32;
-
-    let next_version = current_version + 1;
-    let version_id = Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
-
-    // Create new version
-    let version_data = serde_json::json!({
-        "id": version_id,
-        "project_id": project_id,
-        "version_number": next_version,
-        "prompt": req.prompt,
-        "source_code": req.source_code,
-        "model": req.model,
-        "created_at": now
+        .get("branch")
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_BRANCH)
+        .to_string();
+    let mut fields = serde_json::json!({
+        "prompt": target_doc.data.get("prompt"),
+        "source_code": target_doc.data.get("source_code"),
+        "model": target_doc.data.get("model"),
+        "reverted_from": version_number
     });
+    copy_version_checks(&target_doc, &mut fields).await;
+    let version_doc = append_version(&app_state, &project_id, &branch, fields).await?;
 
+    Ok(Json(version_response(&project_id, version_doc)))
+}
+
+/// Store `fields` as the next version on `branch` and advance the branch head.
+///
+/// Callers check the source first (`check_version_source`, or `copy_version_checks` for
+/// source copied from another version), so its validation and health are saved with it.
+pub(crate) async fn append_version(
+    app_state: &AppState,
+    project_id: &str,
+    branch: &str,
+    fields: serde_json::Value,
+) -> Result<Document, StatusCode> {
     match app_state
         .database
-        .create_document("project_versions", version_data)
+        .append_project_version(project_id, branch, fields)
         .await
     {
-        Ok(version_doc) => {
-            // Update project's current_version
-            if let Some(data_obj) = project_document.data.as_object_mut() {
-                data_obj.insert(
-                    "current_version".to_string(),
-                    serde_json::Value::Number(next_version.into()),
-                );
-                data_obj.insert("updated_at".to_string(), serde_json::Value::String(now));
-            }
-
-            let _ = app_state
-                .database
-                .update_document("projects", &project_document.id, project_document.data)
-                .await;
-
-            let response = ProjectVersionResponse {
-                data: version_doc.into(),
-                links: ProjectVersionResponseLinks {
-                    self_link: format!("/api/projects/{}/versions/{}", project_id, next_version),
-                    project: format!("/api/projects/{}", project_id),
-                },
-            };
-            Ok(Json(response))
+        Ok(Some(version_doc)) => Ok(version_doc),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            tracing::warn!("Version number conflict for project {}: {}", project_id, e);
+            Err(StatusCode::CONFLICT)
         }
         Err(e) => {
             tracing::error!("Failed to create project version: {}", e);
@@ -451,27 +398,42 @@ pub async fn create_version(
     }
 }
 
+/// Find a project document by its data.id field
+pub(crate) async fn find_project(
+    app_state: &AppState,
+    project_id: &str,
+) -> Result<Option<Document>, StatusCode> {
+    let projects = app_state
+        .database
+        .list_documents_where_in("projects", "id", &[project_id.to_string()])
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to find project: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(projects.into_iter().next())
+}
+
+pub(crate) fn version_response(project_id: &str, version_doc: Document) -> ProjectVersionResponse {
+    let version: ProjectVersion = version_doc.into();
+    ProjectVersionResponse {
+        links: ProjectVersionResponseLinks {
+            self_link: format!(
+                "/api/projects/{}/versions/{}",
+                project_id, version.version_number
+            ),
+            project: format!("/api/projects/{}", project_id),
+        },
+        data: version,
+    }
+}
+
 pub async fn list_versions(
     State(app_state): State<AppState>,
     Path(project_id): Path<String>,
 ) -> Result<Json<ProjectVersionListResponse>, StatusCode> {
-    let versions_result = app_state
-        .database
-        .list_documents("project_versions", Some(1000), Some(0))
-        .await;
-    let versions = match versions_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list project versions: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let project_versions: Vec<_> = versions
-        .documents
-        .into_iter()
-        .filter(|doc| doc.data.get("project_id").and_then(|v| v.as_str()) == Some(&project_id))
-        .collect();
+    let project_versions = find_project_versions(&app_state, &project_id).await?;
 
     let count = project_versions.len() as i64;
     let project_id_for_links = project_id.clone();
@@ -490,195 +452,142 @@ pub async fn list_versions(
     Ok(Json(response))
 }
 
-pub async fn release_version(
+pub async fn diff_versions(
     State(app_state): State<AppState>,
-    Path(project_id): Path<String>,
-    JsonBody(req): JsonBody<ReleaseVersionRequest>,
-) -> Result<Json<AppResponse>, StatusCode> {
-    // Get project
-    let projects_result = app_state
-        .database
-        .list_documents("projects", Some(1000), Some(0))
-        .await;
-    let projects = match projects_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let project_doc = projects
-        .documents
-        .into_iter()
-        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&project_id));
-
-    let project_doc = match project_doc {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
+    Path((project_id, from_version, to_version)): Path<(String, i32, i32)>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<ProjectVersionDiffResponse>, StatusCode> {
+    let from_doc = find_project_version(&app_state, &project_id, from_version)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let to_doc = find_project_version(&app_state, &project_id, to_version)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let from_source = from_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or("");
+    let to_source = to_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or("");
+
+    let diff = diff_sources(
+        from_source,
+        to_source,
+        &format!("v{}", from_version),
+        &format!("v{}", to_version),
+        query.context.unwrap_or(3),
+    );
+
+    let response = ProjectVersionDiffResponse {
+        data: ProjectVersionDiff {
+            project_id: project_id.clone(),
+            from_version,
+            to_version,
+            unified: diff.unified,
+            hunks: diff.hunks,
+            stats: diff.stats,
+        },
+        links: ProjectVersionDiffLinks {
+            self_link: format!(
+                "/api/projects/{}/versions/{}/diff/{}",
+                project_id, from_version, to_version
+            ),
+            from: format!("/api/projects/{}/versions/{}", project_id, from_version),
+            to: format!("/api/projects/{}/versions/{}", project_id, to_version),
+            project: format!("/api/projects/{}", project_id),
+        },
     };
+    Ok(Json(response))
+}
 
-    // Get the specific version
-    let versions_result = app_state
+/// All versions of a project in version number order
+pub(crate) async fn find_project_versions(
+    app_state: &AppState,
+    project_id: &str,
+) -> Result<Vec<Document>, StatusCode> {
+    let mut project_versions = app_state
         .database
-        .list_documents("project_versions", Some(1000), Some(0))
-        .await;
-    let versions = match versions_result {
-        Ok(result) => result,
-        Err(e) => {
+        .list_documents_where_in("project_versions", "project_id", &[project_id.to_string()])
+        .await
+        .map_err(|e| {
             tracing::error!("Failed to list project versions: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let version_doc = versions.documents.into_iter().find(|doc| {
-        doc.data.get("project_id").and_then(|v| v.as_str()) == Some(&project_id)
-            && doc.data.get("version_number").and_then(|v| v.as_i64())
-                == Some(req.version_number as i64)
-    });
-
-    let version_doc = match version_doc {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    project_versions.sort_by_key(|doc| doc.data.get("version_number").and_then(|v| v.as_i64()).unwrap_or(0));
+    Ok(project_versions)
+}
 
-    // Create app from project version
-    let app_id = Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
+/// Find a project's version document by its version number
+pub(crate) async fn find_project_version(
+    app_state: &AppState,
+    project_id: &str,
+    version_number: i32,
+) -> Result<Option<Document>, StatusCode> {
+    app_state
+        .database
+        .get_project_version(project_id, version_number)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get project version: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
 
-    let app_data = serde_json::json!({
-        "id": app_id,
-        "name": project_doc.data.get("name").unwrap_or(&serde_json::Value::String("Untitled App".to_string())),
-        "description": project_doc.data.get("description").unwrap_or(&serde_json::Value::String("".to_string())),
-        "version": req.version_number,
-        "price": req.price.unwrap_or(0.0),
-        "icon": project_doc.data.get("icon").unwrap_or(&serde_json::Value::String("📱".to_string())),
-        "installed": 1,
-        "source_code": version_doc.data.get("source_code").unwrap_or(&serde_json::Value::String("".to_string())),
-        "prompt": version_doc.data.get("prompt").unwrap_or(&serde_json::Value::String("".to_string())),
-        "model": version_doc.data.get("model"),
-        "status": "published",
-        "project_id": project_id,
-        "project_version": req.version_number,
-        "created_at": now
-    });
+/// Release a version and return the project's app, as this endpoint always has; `POST
+/// /api/projects/:id/releases` returns the release record instead
+pub async fn release_version(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+    JsonBody(req): JsonBody<ReleaseVersionRequest>,
+) -> Result<Json<AppResponse>, SourceRejection> {
+    let (app_doc, _) = publish_release(&app_state, &project_id, req.into()).await?;
 
-    match app_state.database.create_document("apps", app_data).await {
-        Ok(app_doc) => {
-            let response = AppResponse {
-                data: app_doc.into(),
-                links: AppResponseLinks {
-                    self_link: format!("/api/apps/{}", app_id),
-                },
-            };
-            Ok(Json(response))
+    let app: App = app_doc.into();
+    let response = AppResponse {
+        links: AppResponseLinks {
+            self_link: format!("/api/apps/{}", app.id),
         },
-        Err(e) => {
-            tracing::error!("Failed to release version as app: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        data: app,
+    };
+    Ok(Json(response))
 }
 
 pub async fn convert_to_app(
     State(app_state): State<AppState>,
     Path(project_id): Path<String>,
     JsonBody(req): JsonBody<ConvertToAppRequest>,
-) -> Result<Json<AppResponse>, StatusCode> {
-    // Get project
-    let projects_result = app_state
-        .database
-        .list_documents("projects", Some(1000), Some(0))
-        .await;
-    let projects = match projects_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list projects: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let project_doc = projects
-        .documents
-        .into_iter()
-        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&project_id));
-
-    let project_doc = match project_doc {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
-    };
-
-    // Get the specific version
-    let versions_result = app_state
-        .database
-        .list_documents("project_versions", Some(1000), Some(0))
-        .await;
-    let versions = match versions_result {
-        Ok(result) => result,
-        Err(e) => {
-            tracing::error!("Failed to list project versions: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
-
-    let version_doc = versions.documents.into_iter().find(|doc| {
-        doc.data.get("project_id").and_then(|v| v.as_str()) == Some(&project_id)
-            && doc.data.get("version_number").and_then(|v| v.as_i64()) == Some(req.version as i64)
-    });
-
-    let version_doc = match version_doc {
-        Some(doc) => doc,
-        None => return Err(StatusCode::NOT_FOUND),
+) -> Result<Json<AppResponse>, SourceRejection> {
+    let options = ReleaseOptions {
+        version_number: req.version,
+        price: req.price,
+        version: None,
+        tags: None,
+        notes: None,
+        manifest: None,
     };
+    let (app_doc, _) = publish_release(&app_state, &project_id, options).await?;
 
-    // Create app from project version
-    let app_id = Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
-
-    let app_data = serde_json::json!({
-        "id": app_id,
-        "name": project_doc.data.get("name").unwrap_or(&serde_json::Value::String("Untitled App".to_string())),
-        "description": project_doc.data.get("description").unwrap_or(&serde_json::Value::String("".to_string())),
-        "version": req.version,
-        "price": req.price.unwrap_or(0.0),
-        "icon": project_doc.data.get("icon").unwrap_or(&serde_json::Value::String("📱".to_string())),
-        "installed": 1,
-        "source_code": version_doc.data.get("source_code").unwrap_or(&serde_json::Value::String("".to_string())),
-        "prompt": version_doc.data.get("prompt").unwrap_or(&serde_json::Value::String("".to_string())),
-        "model": version_doc.data.get("model"),
-        "status": "published",
-        "project_id": project_id,
-        "project_version": req.version,
-        "created_at": now
-    });
-
-    match app_state.database.create_document("apps", app_data).await {
-        Ok(app_doc) => {
-            let response = AppResponse {
-                data: app_doc.into(),
-                links: AppResponseLinks {
-                    self_link: format!("/api/apps/{}", app_id),
-                },
-            };
-            Ok(Json(response))
+    let app: App = app_doc.into();
+    let response = AppResponse {
+        links: AppResponseLinks {
+            self_link: format!("/api/apps/{}", app.id),
         },
-        Err(e) => {
-            tracing::error!("Failed to convert project to app: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        data: app,
+    };
+    Ok(Json(response))
 }
 
 pub async fn list_published_projects(
     State(app_state): State<AppState>,
-    Query(query): Query<ListQuery>,
+    Query(query): Query<ListProjectsQuery>,
 ) -> Result<Json<ProjectListResponse>, StatusCode> {
-    // Get all projects from the database (we need to get all first, then filter)
-    let projects_result = app_state
+    let limit = query.limit.unwrap_or(100);
+    let offset = query.offset.unwrap_or(0);
+    let (mut filter, sort) = project_listing(&query)?;
+    filter.status = Some(ProjectStatus::Published.as_str().to_string());
+
+    let result = match app_state
         .database
-        .list_documents("projects", None, None)
-        .await;
-    let projects = match projects_result {
+        .list_filtered_documents("projects", &filter, &sort, Some(limit), Some(offset))
+        .await
+    {
         Ok(result) => result,
         Err(e) => {
             tracing::error!("Failed to list projects: {}", e);
@@ -686,93 +595,50 @@ pub async fn list_published_projects(
         }
     };
 
-    // Get all versions for enrichment
-    let versions_result = app_state
+    // Only load the versions of the projects on this page
+    let project_ids: Vec<String> = result
+        .documents
+        .iter()
+        .filter_map(|doc| doc.data.get("id").and_then(|v| v.as_str()))
+        .map(|id| id.to_string())
+        .collect();
+    let versions = match app_state
         .database
-        .list_documents("project_versions", None, None)
-        .await;
-    let versions = match versions_result {
-        Ok(result) => result,
+        .list_documents_where_in("project_versions", "project_id", &project_ids)
+        .await
+    {
+        Ok(versions) => versions,
         Err(e) => {
             tracing::error!("Failed to list project versions: {}", e);
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
 
-    // Filter for published projects and enrich with version data
-    let all_published_projects: Vec<serde_json::Value> = projects
+    let projects: Vec<crate::models::Project> = result
         .documents
         .into_iter()
-        .filter(|doc| {
-            doc.data
-                .get("status")
-                .and_then(|v| v.as_str())
-                .map(|s| s == "published")
-                .unwrap_or(false)
-        })
         .map(|doc| {
-            let project_id = doc.data.get("id").and_then(|v| v.as_str()).unwrap_or("");
-
-            // Find all versions for this project
-            let project_versions: Vec<_> = versions
-                .documents
-                .iter()
-                .filter(|version_doc| {
-                    version_doc.data.get("project_id").and_then(|v| v.as_str()) == Some(project_id)
-                })
-                .map(|version_doc| &version_doc.data)
-                .collect();
-
-            // Create project data in the format expected by the frontend
-            let project_data = serde_json::json!({
-                "id": doc.data.get("id").unwrap_or(&serde_json::Value::Null),
-                "name": doc.data.get("name").unwrap_or(&serde_json::Value::String("Untitled".to_string())),
-                "description": doc.data.get("description").unwrap_or(&serde_json::Value::String("".to_string())),
-                "icon": doc.data.get("icon").unwrap_or(&serde_json::Value::String("📱".to_string())),
-                "status": doc.data.get("status").unwrap_or(&serde_json::Value::String("published".to_string())),
-                "current_version": doc.data.get("current_version").unwrap_or(&serde_json::Value::Number(0.into())),
-                "initial_prompt": doc.data.get("initial_prompt").unwrap_or(&serde_json::Value::String("".to_string())),
-                "initial_model": doc.data.get("initial_model"),
-                "created_at": doc.data.get("created_at").unwrap_or(&serde_json::Value::String(doc.created_at.to_rfc3339())),
-                "updated_at": doc.data.get("updated_at").unwrap_or(&serde_json::Value::String(doc.updated_at.to_rfc3339())),
-                "versions": project_versions
-            });
-
-            project_data
+            let mut project: crate::models::Project = doc.into();
+            project.versions = Some(
+                versions
+                    .iter()
+                    .filter(|version_doc| {
+                        version_doc.data.get("project_id").and_then(|v| v.as_str())
+                            == Some(project.id.as_str())
+                    })
+                    .map(|version_doc| version_doc.clone().into())
+                    .collect(),
+            );
+            project
         })
         .collect();
 
-    let total_count = all_published_projects.len() as i64;
-
-    // Apply pagination after filtering
-    let limit = query.limit.unwrap_or(100) as usize;
-    let offset = query.offset.unwrap_or(0) as usize;
-
-    let published_projects: Vec<serde_json::Value> = all_published_projects
-        .into_iter()
-        .skip(offset)
-        .take(limit)
-        .collect();
-
-    // Convert serde_json::Value projects to Project structs
-    let projects: Vec<crate::models::Project> = published_projects.into_iter().map(|project_data| {
-        // Create a mock Document to use the From<Document> trait
-        let doc = crate::models::Document {
-            id: project_data.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-            collection: "projects".to_string(),
-            data: project_data,
-            created_at: chrono::Utc::now(),
-            updated_at: chrono::Utc::now(),
-        };
-        doc.into()
-    }).collect();
-
     let response = ProjectListResponse {
         data: projects,
         meta: ProjectListMeta {
-            count: total_count,
-            limit: query.limit.unwrap_or(100),
-            offset: query.offset.unwrap_or(0),
+            count: result.count,
+            limit,
+            offset,
         },
         links: ProjectListLinks {
             self_link: "/api/published-projects".to_string(),
@@ -781,3 +647,24 @@ pub async fn list_published_projects(
     };
     Ok(Json(response))
 }
+
+/// Search, filters and sort shared by the project listings
+fn project_listing(query: &ListProjectsQuery) -> Result<(DocumentFilter, DocumentSort), StatusCode> {
+    let mut filter = DocumentFilter {
+        search: query.q.clone().filter(|q| !q.trim().is_empty()),
+        created_after: parse_date_param(query.created_after.clone()).map_err(|_| StatusCode::BAD_REQUEST)?,
+        created_before: parse_date_param(query.created_before.clone()).map_err(|_| StatusCode::BAD_REQUEST)?,
+        updated_after: parse_date_param(query.updated_after.clone()).map_err(|_| StatusCode::BAD_REQUEST)?,
+        updated_before: parse_date_param(query.updated_before.clone()).map_err(|_| StatusCode::BAD_REQUEST)?,
+        ..Default::default()
+    };
+    if let Some(model) = &query.model {
+        filter.equals.push(("initial_model", model.clone()));
+    }
+
+    let sort = DocumentSort {
+        field: query.sort,
+        order: query.order,
+    };
+    Ok((filter, sort))
+}
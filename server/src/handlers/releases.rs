@@ -0,0 +1,375 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Json as JsonBody,
+};
+use semver::Version;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::projects::{find_project, find_project_version, ReleaseVersionRequest};
+use super::sources::SourceRejection;
+use crate::capabilities::{infer_manifest, validate_manifest};
+use crate::models::{
+    App, AppManifest, AppReleaseRef, Document, Release, ReleaseListLinks, ReleaseListResponse, ReleaseResponse,
+    ReleaseResponseLinks,
+};
+use crate::validation::validate_source;
+use crate::AppState;
+
+pub async fn list_releases(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<ReleaseListResponse>, StatusCode> {
+    if find_project(&app_state, &project_id).await?.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let releases = find_releases(&app_state, &project_id).await?;
+
+    let response = ReleaseListResponse {
+        data: releases.into_iter().map(|doc| doc.into()).collect(),
+        links: ReleaseListLinks {
+            self_link: format!("/api/projects/{}/releases", project_id),
+            project: format!("/api/projects/{}", project_id),
+        },
+    };
+    Ok(Json(response))
+}
+
+/// Release a project version, returning the new release record
+pub async fn create_release(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+    JsonBody(req): JsonBody<ReleaseVersionRequest>,
+) -> Result<Json<ReleaseResponse>, SourceRejection> {
+    let (_, release_doc) = publish_release(&app_state, &project_id, req.into()).await?;
+
+    Ok(Json(release_response(release_doc)))
+}
+
+/// What to publish when releasing a project version
+pub(crate) struct ReleaseOptions {
+    pub version_number: i32,
+    pub price: Option<f64>,
+    pub version: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub notes: Option<String>,
+    pub manifest: Option<AppManifest>,
+}
+
+impl From<ReleaseVersionRequest> for ReleaseOptions {
+    fn from(req: ReleaseVersionRequest) -> Self {
+        ReleaseOptions {
+            version_number: req.version_number,
+            price: req.price,
+            version: req.version,
+            tags: req.tags,
+            notes: req.notes,
+            manifest: req.manifest,
+        }
+    }
+}
+
+/// Record a release of a project version and publish it to the project's app.
+///
+/// Each project has a single app: the first release creates it with the release's source
+/// and grants its manifest straight away, since it's the user's own project. Later releases
+/// refresh the app's name, description and icon but leave its source alone, flagging the
+/// update on an installed copy, which then switches over through `upgrade_app`. Returns the
+/// app and the new release record.
+pub(crate) async fn publish_release(
+    app_state: &AppState,
+    project_id: &str,
+    options: ReleaseOptions,
+) -> Result<(Document, Document), SourceRejection> {
+    let tags = normalize_tags(options.tags)?;
+
+    let project_doc = find_project(app_state, project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let version_doc = find_project_version(app_state, project_id, options.version_number)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Checked again rather than trusting the version's record, which may predate a check
+    let validation = validate_source(
+        version_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or(""),
+    );
+    if validation.has_errors() {
+        return Err(SourceRejection::Invalid(validation));
+    }
+
+    let manifest = match options.manifest {
+        Some(manifest) => validate_manifest(manifest)?,
+        None => infer_manifest(
+            version_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or(""),
+        ),
+    };
+
+    let release_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    let changelog = version_doc
+        .data
+        .get("changelog")
+        .and_then(|c| c.get("text"))
+        .and_then(|v| v.as_str());
+    // Without notes of its own the release reads the version's changelog
+    let notes = options
+        .notes
+        .filter(|notes| !notes.trim().is_empty())
+        .or(changelog.map(|text| text.to_string()))
+        .unwrap_or_default();
+
+    // The release's semver is checked against earlier releases, and the app looked up,
+    // in the same transaction that records both; without a price the app keeps its own
+    let release_data = serde_json::json!({
+        "id": release_id,
+        "project_id": project_id,
+        "project_version": options.version_number,
+        "tags": tags,
+        "notes": notes,
+        "price": options.price,
+        "changelog": changelog,
+        "manifest": manifest,
+        "created_at": now
+    });
+    let app_data = serde_json::json!({
+        "name": project_doc.data.get("name").unwrap_or(&serde_json::Value::String("Untitled App".to_string())),
+        "description": project_doc.data.get("description").unwrap_or(&serde_json::Value::String("".to_string())),
+        "icon": project_doc.data.get("icon").unwrap_or(&serde_json::Value::String("📱".to_string())),
+        "source_code": version_doc.data.get("source_code").unwrap_or(&serde_json::Value::String("".to_string())),
+        "prompt": version_doc.data.get("prompt").unwrap_or(&serde_json::Value::String("".to_string())),
+        "model": version_doc.data.get("model"),
+        "status": "published",
+        "project_id": project_id,
+        "project_version": options.version_number,
+        "release_id": release_id,
+        "manifest": manifest,
+        "validation": validation,
+        "installed": true,
+        "granted": manifest,
+        "granted_at": now,
+        "created_at": now,
+        "updated_at": now
+    });
+
+    match app_state
+        .database
+        .insert_release(project_id, release_data, app_data, |released| {
+            next_release_version(released, options.version.as_deref())
+        })
+        .await
+    {
+        Ok(Ok(published)) => Ok(published),
+        Ok(Err(status)) => Err(status.into()),
+        Err(e) => {
+            tracing::error!("Failed to release version as app: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into())
+        }
+    }
+}
+
+/// Switch an app to an earlier or later release of its project.
+///
+/// Only the app's source, version, price, manifest and release pointers change; documents
+/// the app stored in its data collections are left as they are. Capabilities the release
+/// asks for beyond what the user granted need their consent before the app can use them.
+pub(crate) async fn install_release(
+    app_state: &AppState,
+    app_doc: Document,
+    release: AppReleaseRef,
+) -> Result<Document, StatusCode> {
+    let app_doc = apply_release(app_state, app_doc, release).await?;
+
+    match app_state
+        .database
+        .update_document("apps", &app_doc.id, app_doc.data)
+        .await
+    {
+        Ok(Some(updated_doc)) => Ok(updated_doc),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to switch app release: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Put a release's source, version, price and manifest on an app document without saving it
+pub(crate) async fn apply_release(
+    app_state: &AppState,
+    mut app_doc: Document,
+    release: AppReleaseRef,
+) -> Result<Document, StatusCode> {
+    let previous: App = app_doc.clone().into();
+    let project_id = previous.project_id.clone().ok_or(StatusCode::BAD_REQUEST)?;
+
+    let version_doc = find_project_version(app_state, &project_id, release.project_version)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let release_record: Option<Release> = match &release.release_id {
+        Some(release_id) => find_releases(app_state, &project_id)
+            .await?
+            .into_iter()
+            .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(release_id.as_str()))
+            .map(Release::from),
+        None => None,
+    };
+    if let (Some(data_obj), Some(release_record)) = (app_doc.data.as_object_mut(), &release_record) {
+        data_obj.insert("price".to_string(), serde_json::json!(release_record.price));
+    }
+    let manifest = release_record.and_then(|r| r.manifest).unwrap_or_else(|| {
+        infer_manifest(version_doc.data.get("source_code").and_then(|v| v.as_str()).unwrap_or(""))
+    });
+
+    if let Some(data_obj) = app_doc.data.as_object_mut() {
+        data_obj.insert("manifest".to_string(), serde_json::json!(manifest));
+        data_obj.insert("source_code".to_string(), serde_json::json!(version_doc.data.get("source_code")));
+        data_obj.insert("validation".to_string(), serde_json::json!(version_doc.data.get("validation")));
+        data_obj.insert("prompt".to_string(), serde_json::json!(version_doc.data.get("prompt")));
+        data_obj.insert("model".to_string(), serde_json::json!(version_doc.data.get("model")));
+        data_obj.insert("version".to_string(), serde_json::json!(release.version));
+        data_obj.insert("project_version".to_string(), serde_json::json!(release.project_version));
+        data_obj.insert("release_id".to_string(), serde_json::json!(release.release_id));
+        if let Some(project_version) = previous.project_version {
+            data_obj.insert(
+                "previous_release".to_string(),
+                serde_json::json!({
+                    "release_id": previous.release_id,
+                    "version": previous.version,
+                    "project_version": project_version
+                }),
+            );
+        }
+        data_obj.remove("update_notice");
+        data_obj.insert(
+            "updated_at".to_string(),
+            serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+    Ok(app_doc)
+}
+
+/// The highest-semver release of each of the given projects, keyed by project id
+pub(crate) async fn latest_releases(
+    app_state: &AppState,
+    project_ids: &[String],
+) -> Result<HashMap<String, Document>, StatusCode> {
+    let releases = app_state
+        .database
+        .list_documents_where_in("releases", "project_id", project_ids)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list releases: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut latest: HashMap<String, Document> = HashMap::new();
+    for doc in releases {
+        let project_id = match doc.data.get("project_id").and_then(|v| v.as_str()) {
+            Some(project_id) => project_id.to_string(),
+            None => continue,
+        };
+        let newer = match latest.get(&project_id) {
+            Some(current) => release_semver(&doc) > release_semver(current),
+            None => true,
+        };
+        if newer {
+            latest.insert(project_id, doc);
+        }
+    }
+    Ok(latest)
+}
+
+/// Reference to a release record, as stored on an app
+pub(crate) fn release_ref(release_doc: &Document) -> AppReleaseRef {
+    let release: Release = release_doc.clone().into();
+    AppReleaseRef {
+        release_id: Some(release.id),
+        version: release.version,
+        project_version: release.project_version,
+    }
+}
+
+/// All release records of a project, newest semver first
+pub(crate) async fn find_releases(
+    app_state: &AppState,
+    project_id: &str,
+) -> Result<Vec<Document>, StatusCode> {
+    let mut project_releases = app_state
+        .database
+        .list_documents_where_in("releases", "project_id", &[project_id.to_string()])
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list releases: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    project_releases.sort_by_key(|doc| std::cmp::Reverse(release_semver(doc)));
+    Ok(project_releases)
+}
+
+/// Pick the semver for a new release: `requested` must be greater than every earlier
+/// release, and when omitted the latest release gets a patch bump (or a pre-release is
+/// promoted to its final version)
+pub(crate) fn next_release_version(
+    released: &[Version],
+    requested: Option<&str>,
+) -> Result<Version, StatusCode> {
+    let latest = released.iter().max().cloned();
+
+    match requested {
+        Some(requested) => {
+            let version = Version::parse(requested.trim()).map_err(|_| StatusCode::BAD_REQUEST)?;
+            match latest {
+                Some(latest) if version <= latest => Err(StatusCode::CONFLICT),
+                _ => Ok(version),
+            }
+        }
+        None => Ok(match latest {
+            Some(latest) if !latest.pre.is_empty() => {
+                Version::new(latest.major, latest.minor, latest.patch)
+            }
+            Some(latest) => Version::new(latest.major, latest.minor, latest.patch + 1),
+            None => Version::new(1, 0, 0),
+        }),
+    }
+}
+
+/// Normalize release tags, rejecting anything but short lowercase slugs like `beta`
+pub(crate) fn normalize_tags(tags: Option<Vec<String>>) -> Result<Vec<String>, StatusCode> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.unwrap_or_default() {
+        let tag = tag.trim().to_lowercase();
+        let valid = !tag.is_empty()
+            && tag.len() <= 32
+            && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+pub(crate) fn release_response(release_doc: Document) -> ReleaseResponse {
+    let release: Release = release_doc.into();
+    ReleaseResponse {
+        links: ReleaseResponseLinks {
+            self_link: format!("/api/projects/{}/releases", release.project_id),
+            project: format!("/api/projects/{}", release.project_id),
+            app: release.app_id.as_ref().map(|app_id| format!("/api/apps/{}", app_id)),
+        },
+        data: release,
+    }
+}
+
+fn release_semver(doc: &Document) -> Option<Version> {
+    doc.data
+        .get("version")
+        .and_then(|v| v.as_str())
+        .and_then(|s| Version::parse(s).ok())
+}
@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Json as JsonBody,
+};
+
+use super::projects::find_project;
+use crate::models::{
+    PruneReportLinks, PruneReportResponse, RetentionPolicy, RetentionPolicyLinks,
+    RetentionPolicyResponse,
+};
+use crate::AppState;
+
+pub async fn get_retention_policy(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<RetentionPolicyResponse>, StatusCode> {
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let policy = project_doc
+        .data
+        .get("retention")
+        .and_then(|v| serde_json::from_value(v.clone()).ok());
+    Ok(Json(policy_response(&project_id, policy)))
+}
+
+pub async fn update_retention_policy(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+    JsonBody(policy): JsonBody<RetentionPolicy>,
+) -> Result<Json<RetentionPolicyResponse>, StatusCode> {
+    save_policy(&app_state, &project_id, Some(&policy)).await?;
+    Ok(Json(policy_response(&project_id, Some(policy))))
+}
+
+/// Remove a project's retention policy so pruning keeps every version again
+pub async fn delete_retention_policy(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    save_policy(&app_state, &project_id, None).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Show what pruning would remove without deleting anything
+pub async fn preview_pruning(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<PruneReportResponse>, StatusCode> {
+    prune(&app_state, &project_id, true).await
+}
+
+/// Delete the versions the project's retention policy doesn't keep
+pub async fn prune_versions(
+    State(app_state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<PruneReportResponse>, StatusCode> {
+    prune(&app_state, &project_id, false).await
+}
+
+async fn prune(
+    app_state: &AppState,
+    project_id: &str,
+    dry_run: bool,
+) -> Result<Json<PruneReportResponse>, StatusCode> {
+    let report = app_state
+        .database
+        .prune_project_versions(project_id, dry_run)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to prune project versions: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(PruneReportResponse {
+        data: report,
+        links: PruneReportLinks {
+            project: format!("/api/projects/{}", project_id),
+            versions: format!("/api/projects/{}/versions", project_id),
+            retention: format!("/api/projects/{}/retention", project_id),
+        },
+    }))
+}
+
+async fn save_policy(
+    app_state: &AppState,
+    project_id: &str,
+    policy: Option<&RetentionPolicy>,
+) -> Result<(), StatusCode> {
+    let project_doc = find_project(app_state, project_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let fields = vec![
+        ("retention", policy.map(|policy| serde_json::json!(policy))),
+        ("updated_at", Some(serde_json::json!(chrono::Utc::now().to_rfc3339()))),
+    ];
+    match app_state
+        .database
+        .patch_document("projects", &project_doc.id, fields)
+        .await
+    {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to save retention policy: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn policy_response(project_id: &str, policy: Option<RetentionPolicy>) -> RetentionPolicyResponse {
+    RetentionPolicyResponse {
+        data: policy,
+        links: RetentionPolicyLinks {
+            self_link: format!("/api/projects/{}/retention", project_id),
+            project: format!("/api/projects/{}", project_id),
+            preview: format!("/api/projects/{}/retention/preview", project_id),
+        },
+    }
+}
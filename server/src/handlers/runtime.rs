@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::Html,
+};
+
+use super::apps::find_app;
+use crate::models::App;
+use crate::runtime::{
+    app_icon_svg, app_manifest, react_bundle, render_export_page, render_run_page, run_page_policy,
+};
+use crate::AppState;
+
+/// Run an app on its own page, outside the dashboard.
+///
+/// The page is sandboxed into an origin of its own and may only connect to this server and
+/// the network hosts the app was granted. It gets no token itself: the dashboard issues one
+/// for each launch and passes it in the URL fragment.
+pub async fn run_app(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<([(header::HeaderName, String); 1], Html<String>), StatusCode> {
+    let app = runnable_app(&app_state, &app_id).await?;
+    Ok((
+        [(header::CONTENT_SECURITY_POLICY, run_page_policy(&app))],
+        Html(render_run_page(&app)),
+    ))
+}
+
+/// Web app manifest for an app's standalone page
+pub async fn get_app_manifest(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), StatusCode> {
+    let app = runnable_app(&app_state, &app_id).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/manifest+json")],
+        app_manifest(&app).to_string(),
+    ))
+}
+
+/// Icon for an app's standalone page and manifest
+pub async fn get_app_icon(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), StatusCode> {
+    let app = runnable_app(&app_state, &app_id).await?;
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], app_icon_svg(&app)))
+}
+
+/// Export an app as one self-contained HTML file that runs offline, keeping its data in the
+/// browser's localStorage
+pub async fn export_app_html(
+    State(app_state): State<AppState>,
+    Path(app_id): Path<String>,
+) -> Result<([(header::HeaderName, String); 2], String), StatusCode> {
+    let app = runnable_app(&app_state, &app_id).await?;
+    if app.source_code.is_none() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let react = react_bundle().await.map_err(|e| {
+        tracing::error!("Failed to download React for app export: {}", e);
+        StatusCode::BAD_GATEWAY
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.html\"", export_file_stem(&app.id)),
+            ),
+        ],
+        render_export_page(&app, react),
+    ))
+}
+
+/// An app whose pages may be served: only installed apps run, as with their tokens
+async fn runnable_app(app_state: &AppState, app_id: &str) -> Result<App, StatusCode> {
+    let app_doc = find_app(app_state, app_id).await?.ok_or(StatusCode::NOT_FOUND)?;
+    let app: App = app_doc.into();
+    if !app.installed {
+        return Err(StatusCode::CONFLICT);
+    }
+    Ok(app)
+}
+
+/// App ids come from users, so keep only characters that are safe in a file name
+fn export_file_stem(app_id: &str) -> String {
+    let stem: String = app_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    if stem.is_empty() {
+        "app".to_string()
+    } else {
+        stem
+    }
+}
@@ -0,0 +1,114 @@
+use aide::OperationOutput;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Json, Response},
+    Json as JsonBody,
+};
+
+use crate::capabilities::infer_manifest;
+use crate::models::{
+    App, Document, PreviewSourceRequest, SourceValidation, SourceValidationLinks, SourceValidationResponse,
+    ValidateSourceRequest,
+};
+use crate::runtime::{react_bundle, render_export_page};
+use crate::sources::is_source_hash;
+use crate::validation::validate_source;
+use crate::AppState;
+
+/// Fetch source code by its SHA-256, so clients and peers can check what they were given
+pub async fn get_source(
+    State(app_state): State<AppState>,
+    Path(hash): Path<String>,
+) -> Result<([(header::HeaderName, String); 2], String), StatusCode> {
+    let hash = hash.to_lowercase();
+    if !is_source_hash(&hash) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match app_state.database.get_source(&hash).await {
+        Ok(Some(source_code)) => Ok((
+            [
+                (header::CONTENT_TYPE, "text/javascript; charset=utf-8".to_string()),
+                // The content can never change for a given hash
+                (header::ETAG, format!("\"{}\"", hash)),
+            ],
+            source_code,
+        )),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to get source: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Check app source without saving it, returning the diagnostics that would block a release
+pub async fn validate_app_source(
+    JsonBody(req): JsonBody<ValidateSourceRequest>,
+) -> Json<SourceValidationResponse> {
+    Json(SourceValidationResponse {
+        data: validate_source(&req.source_code),
+        links: SourceValidationLinks {
+            self_link: "/api/sources/validate".to_string(),
+        },
+    })
+}
+
+/// Render app source as a self-contained page for the dashboard to preview in a sandboxed
+/// frame. The page has no host API token; its data lives in the page and is gone when it
+/// closes.
+pub async fn preview_app_source(
+    JsonBody(req): JsonBody<PreviewSourceRequest>,
+) -> Result<Html<String>, StatusCode> {
+    let now = chrono::Utc::now();
+    let app: App = Document {
+        id: "preview".to_string(),
+        collection: "apps".to_string(),
+        data: serde_json::json!({
+            "id": "preview",
+            "name": req.name.unwrap_or_else(|| "Preview".to_string()),
+            "manifest": infer_manifest(&req.source_code),
+            "source_code": req.source_code,
+            "installed": false
+        }),
+        created_at: now,
+        updated_at: now,
+    }
+    .into();
+
+    let react = react_bundle().await.map_err(|e| {
+        tracing::error!("Failed to download React for app preview: {}", e);
+        StatusCode::BAD_GATEWAY
+    })?;
+    Ok(Html(render_export_page(&app, react)))
+}
+
+/// Error from a handler that saves or publishes app source: a plain status, or source that
+/// failed validation, whose diagnostics are sent back as the body of a `422`
+#[derive(Debug)]
+pub enum SourceRejection {
+    Status(StatusCode),
+    Invalid(SourceValidation),
+}
+
+impl From<StatusCode> for SourceRejection {
+    fn from(status: StatusCode) -> Self {
+        SourceRejection::Status(status)
+    }
+}
+
+impl IntoResponse for SourceRejection {
+    fn into_response(self) -> Response {
+        match self {
+            SourceRejection::Status(status) => status.into_response(),
+            SourceRejection::Invalid(validation) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, Json(validation)).into_response()
+            }
+        }
+    }
+}
+
+impl OperationOutput for SourceRejection {
+    type Inner = SourceValidation;
+}
@@ -0,0 +1,225 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Json as JsonBody,
+};
+use uuid::Uuid;
+
+use super::health::check_version_source;
+use super::projects::{append_version, find_project, find_project_version};
+use crate::models::{
+    CreateFromTemplateRequest, CreateTemplateRequest, Document, ProjectResponse,
+    ProjectResponseLinks, Template, TemplateListLinks, TemplateListResponse, TemplateResponse,
+    TemplateResponseLinks, DEFAULT_BRANCH,
+};
+use crate::templates::{builtin_template, BUILTIN_TEMPLATES};
+use crate::AppState;
+
+/// List built-in templates followed by user-saved ones, without their source code
+pub async fn list_templates(
+    State(app_state): State<AppState>,
+) -> Result<Json<TemplateListResponse>, StatusCode> {
+    let mut templates: Vec<Template> = BUILTIN_TEMPLATES.iter().map(Template::from).collect();
+    templates.extend(
+        find_saved_templates(&app_state)
+            .await?
+            .into_iter()
+            .map(Template::from),
+    );
+    for template in &mut templates {
+        template.source_code = None;
+    }
+
+    Ok(Json(TemplateListResponse {
+        data: templates,
+        links: TemplateListLinks {
+            self_link: "/api/templates".to_string(),
+        },
+    }))
+}
+
+pub async fn get_template(
+    State(app_state): State<AppState>,
+    Path(template_id): Path<String>,
+) -> Result<Json<TemplateResponse>, StatusCode> {
+    let template = find_template(&app_state, &template_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(template_response(template)))
+}
+
+/// Save a user template, either from the given source or from an existing project version
+pub async fn create_template(
+    State(app_state): State<AppState>,
+    JsonBody(req): JsonBody<CreateTemplateRequest>,
+) -> Result<Json<TemplateResponse>, StatusCode> {
+    if req.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let source_code = match (req.source_code, req.project_id, req.version_number) {
+        (Some(source_code), None, None) => source_code,
+        (None, Some(project_id), Some(version_number)) => {
+            let version_doc = find_project_version(&app_state, &project_id, version_number)
+                .await?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            version_doc
+                .data
+                .get("source_code")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        }
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    if source_code.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let template_data = serde_json::json!({
+        "id": Uuid::new_v4().to_string(),
+        "name": req.name.trim(),
+        "description": req.description.unwrap_or_default(),
+        "icon": req.icon.unwrap_or_else(|| "📋".to_string()),
+        "source_code": source_code
+    });
+
+    match app_state
+        .database
+        .create_document("templates", template_data)
+        .await
+    {
+        Ok(template_doc) => Ok(Json(template_response(template_doc.into()))),
+        Err(e) => {
+            tracing::error!("Failed to create template: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Delete a user template; built-in templates can't be deleted
+pub async fn delete_template(
+    State(app_state): State<AppState>,
+    Path(template_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    if builtin_template(&template_id).is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let template_doc = find_saved_templates(&app_state)
+        .await?
+        .into_iter()
+        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(&template_id))
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    match app_state
+        .database
+        .delete_document("templates", &template_doc.id)
+        .await
+    {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            tracing::error!("Failed to delete template: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Create a draft project whose first version is the template's source code
+pub async fn create_project_from_template(
+    State(app_state): State<AppState>,
+    Path(template_id): Path<String>,
+    JsonBody(req): JsonBody<CreateFromTemplateRequest>,
+) -> Result<Json<ProjectResponse>, StatusCode> {
+    let template = find_template(&app_state, &template_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let project_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let project_data = serde_json::json!({
+        "id": project_id,
+        "name": req.name.unwrap_or_else(|| template.name.clone()),
+        "description": req.description.unwrap_or_else(|| template.description.clone()),
+        "icon": req.icon.unwrap_or_else(|| template.icon.clone()),
+        "status": "draft",
+        "current_version": 0,
+        "initial_prompt": "",
+        "template_id": template.id,
+        "created_at": now,
+        "updated_at": now
+    });
+
+    if let Err(e) = app_state
+        .database
+        .create_document("projects", project_data)
+        .await
+    {
+        tracing::error!("Failed to create project: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let mut fields = serde_json::json!({
+        "prompt": format!("Created from template {}", template.name),
+        "source_code": template.source_code,
+        "template_id": template.id
+    });
+    check_version_source(&mut fields).await;
+    append_version(&app_state, &project_id, DEFAULT_BRANCH, fields).await?;
+
+    // Re-read so the response reflects the version just added
+    let project_doc = find_project(&app_state, &project_id)
+        .await?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(ProjectResponse {
+        data: project_doc.into(),
+        links: ProjectResponseLinks {
+            self_link: format!("/api/projects/{}", project_id),
+            versions: Some(format!("/api/projects/{}/versions", project_id)),
+        },
+    }))
+}
+
+/// Look a template up by id, built-ins first
+async fn find_template(
+    app_state: &AppState,
+    template_id: &str,
+) -> Result<Option<Template>, StatusCode> {
+    if let Some(builtin) = builtin_template(template_id) {
+        return Ok(Some(builtin.into()));
+    }
+
+    Ok(find_saved_templates(app_state)
+        .await?
+        .into_iter()
+        .find(|doc| doc.data.get("id").and_then(|v| v.as_str()) == Some(template_id))
+        .map(Template::from))
+}
+
+async fn find_saved_templates(app_state: &AppState) -> Result<Vec<Document>, StatusCode> {
+    let mut templates = app_state
+        .database
+        .list_documents("templates", Some(1000), Some(0))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list templates: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .documents;
+    // Oldest first, after the built-ins
+    templates.reverse();
+    Ok(templates)
+}
+
+fn template_response(template: Template) -> TemplateResponse {
+    TemplateResponse {
+        links: TemplateResponseLinks {
+            self_link: format!("/api/templates/{}", template.id),
+            create_project: format!("/api/projects/from-template/{}", template.id),
+        },
+        data: template,
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use rquickjs::{Context, Runtime};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::models::{AppHealth, HealthStatus};
+
+/// Evaluates the app source and renders it against stub React, ui, toast and hostAPI objects
+const SMOKE_TEST_SCRIPT: &str = include_str!("../runtime/smoke-test.js");
+
+/// Memory the engine may use while smoke testing one app
+const SMOKE_TEST_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// What the smoke test script reports
+#[derive(Deserialize)]
+struct SmokeTestOutcome {
+    ok: bool,
+    stage: Option<String>,
+    message: Option<String>,
+}
+
+/// Run app source in a fresh JavaScript engine: it must return a function component whose
+/// first render doesn't throw.
+///
+/// The engine has no network, DOM or host access, and is stopped after
+/// `SMOKE_TEST_TIMEOUT_MS`, so source that loops forever is reported rather than hanging.
+pub async fn smoke_test(source_code: String) -> AppHealth {
+    let started = Instant::now();
+    let outcome = tokio::task::spawn_blocking(move || run_smoke_test(&source_code))
+        .await
+        .unwrap_or_else(|e| Err(("evaluate".to_string(), format!("Smoke test crashed: {}", e))));
+
+    let (status, stage, message) = match outcome {
+        Ok(()) => (HealthStatus::Healthy, None, None),
+        Err((stage, message)) => (HealthStatus::Failing, Some(stage), Some(message)),
+    };
+    AppHealth {
+        status,
+        stage,
+        message,
+        duration_ms: started.elapsed().as_millis() as u64,
+        checked_at: Utc::now(),
+    }
+}
+
+/// Returns the failing stage and its error message
+fn run_smoke_test(source_code: &str) -> Result<(), (String, String)> {
+    let engine_error = |e: rquickjs::Error| ("evaluate".to_string(), format!("JavaScript engine error: {}", e));
+
+    let runtime = Runtime::new().map_err(engine_error)?;
+    runtime.set_memory_limit(SMOKE_TEST_MEMORY_LIMIT);
+    let timeout = Duration::from_millis(Config::smoke_test_timeout_ms());
+    let deadline = Instant::now() + timeout;
+    runtime.set_interrupt_handler(Some(Box::new(move || Instant::now() > deadline)));
+    let context = Context::full(&runtime).map_err(engine_error)?;
+
+    context.with(|ctx| {
+        ctx.globals().set("__appSource", source_code).map_err(engine_error)?;
+
+        let report: String = match ctx.eval(SMOKE_TEST_SCRIPT) {
+            Ok(report) => report,
+            Err(rquickjs::Error::Exception) if Instant::now() > deadline => {
+                return Err((
+                    "timeout".to_string(),
+                    format!("Still running after {} ms", timeout.as_millis()),
+                ));
+            }
+            Err(rquickjs::Error::Exception) => {
+                // Only errors the script can't catch get here, such as running out of memory
+                let exception = ctx.catch();
+                let message = exception
+                    .as_exception()
+                    .and_then(|e| e.message())
+                    .unwrap_or_else(|| "Uncaught exception".to_string());
+                return Err(("evaluate".to_string(), message));
+            }
+            Err(e) => return Err(engine_error(e)),
+        };
+
+        let outcome: SmokeTestOutcome = serde_json::from_str(&report)
+            .map_err(|e| ("evaluate".to_string(), format!("Unreadable smoke test report: {}", e)))?;
+        match outcome.ok {
+            true => Ok(()),
+            false => Err((
+                outcome.stage.unwrap_or_else(|| "evaluate".to_string()),
+                outcome.message.unwrap_or_default(),
+            )),
+        }
+    })
+}
@@ -1,28 +1,49 @@
 #![recursion_limit = "512"]
 
 use aide::axum::{
-    routing::{delete, get, get_with, post, post_with, put, put_with},
+    routing::{delete, delete_with, get, get_with, post, post_with, put, put_with},
     ApiRouter, IntoApiResponse,
 };
 use aide::openapi::OpenApi;
 use axum::http::{Method, StatusCode};
-use axum::{response::Redirect, Extension, Json, Router};
+use axum::{extract::DefaultBodyLimit, middleware, response::Redirect, Extension, Json, Router};
 use reqwest::Client;
 use std::{env, sync::Arc};
 use tower_http::cors::{Any, CorsLayer};
+use uuid::Uuid;
+
+use crate::config::Config;
 
 pub mod ai;
+pub mod app_tokens;
+pub mod archive;
+pub mod capabilities;
 pub mod config;
 pub mod database;
+pub mod diff;
 pub mod handlers;
+pub mod health;
+pub mod listing;
+pub mod maintenance;
 pub mod models;
 pub mod openapi;
+pub mod releases;
+pub mod retention;
+pub mod runtime;
+pub mod schema;
 pub mod seed;
+pub mod snapshots;
+pub mod sources;
+pub mod templates;
+pub mod validation;
+pub mod versions;
 
 #[derive(Clone)]
 pub struct AppState {
     pub client: Client,
     pub database: Arc<database::Database>,
+    /// Bearer token only the dashboard holds; app code never sees it
+    pub dashboard_token: Arc<str>,
 }
 
 pub async fn redirect_to_frontend() -> Result<Redirect, StatusCode> {
@@ -49,7 +70,25 @@ pub fn create_router(database: Arc<database::Database>) -> Router {
 
     let mut api = openapi::create_openapi_spec();
 
-    let api_router = create_api_router();
+    let dashboard_token = Config::dashboard_token().unwrap_or_else(|| {
+        let token = Uuid::new_v4().simple().to_string();
+        tracing::warn!(
+            "DASHBOARD_TOKEN isn't set; the dashboard can sign in with {} until the server restarts",
+            token
+        );
+        token
+    });
+    let app_state = AppState {
+        client: Client::new(),
+        database,
+        dashboard_token: dashboard_token.into(),
+    };
+
+    // Everything under /api but the host API is the dashboard's alone
+    let api_router = create_api_router().layer(middleware::from_fn_with_state(
+        app_state.clone(),
+        capabilities::require_dashboard_token,
+    ));
 
     let app = ApiRouter::new()
         .api_route(
@@ -58,7 +97,7 @@ pub fn create_router(database: Arc<database::Database>) -> Router {
                 op.description("Get OpenAPI specification")
             }),
         )
-        .nest("/api", api_router.into())
+        .nest("/api", api_router)
         .finish_api(&mut api)
         .layer(Extension(api))
         .merge(
@@ -68,13 +107,17 @@ pub fn create_router(database: Arc<database::Database>) -> Router {
                 .route(
                     "/generate/modify",
                     axum::routing::post(ai::modify_code_stream),
-                ),
+                )
+                // Standalone app pages are HTML, so they sit outside the documented API
+                .route("/apps/:app_id/run", axum::routing::get(handlers::run_app))
+                .route(
+                    "/apps/:app_id/manifest.webmanifest",
+                    axum::routing::get(handlers::get_app_manifest),
+                )
+                .route("/apps/:app_id/icon.svg", axum::routing::get(handlers::get_app_icon)),
         )
         .layer(cors)
-        .with_state(AppState {
-            client: Client::new(),
-            database,
-        });
+        .with_state(app_state);
 
     app
 }
@@ -87,15 +130,43 @@ fn create_api_router() -> ApiRouter<AppState> {
         .api_route("/db", get(handlers::list_collections))
         .api_route("/db/:collection", post(handlers::create_document))
         .api_route("/db/:collection", get(handlers::list_documents))
+        .api_route("/db/:collection/_schema", get(handlers::get_collection_schema))
         .api_route("/db/:collection/:id", get(handlers::get_document))
         .api_route("/db/:collection/:id", put(handlers::update_document))
         .api_route("/db/:collection/:id", delete(handlers::delete_document))
+        .api_route("/db/reset/prepare", post(handlers::prepare_reset))
         .api_route("/db/reset", post(handlers::reset_database))
         .api_route("/query", post(handlers::execute_query))
         .api_route("/reset", post(handlers::reset_database))
+        // Database maintenance endpoints
+        .api_route("/admin/db/stats", get(handlers::get_database_stats))
+        .api_route("/admin/db/vacuum", post(handlers::vacuum_database))
+        .api_route("/admin/db/analyze", post(handlers::analyze_database))
+        .api_route(
+            "/admin/db/collect-sources",
+            post(handlers::collect_sources),
+        )
+        .api_route(
+            "/admin/db/integrity",
+            get(handlers::check_database_integrity),
+        )
+        // Snapshot endpoints
+        .api_route("/snapshots", get(handlers::list_snapshots))
+        .api_route(
+            "/snapshots/:snapshot_id/restore",
+            post(handlers::restore_snapshot),
+        )
         // Project endpoints
         .api_route("/projects", post(handlers::create_project))
         .api_route("/projects", get(handlers::list_projects))
+        .api_route(
+            "/projects/import",
+            post(handlers::import_project).layer(DefaultBodyLimit::max(archive::MAX_ARCHIVE_BYTES)),
+        )
+        .api_route(
+            "/projects/from-template/:template_id",
+            post(handlers::create_project_from_template),
+        )
         .api_route("/projects/:project_id", get(handlers::get_project))
         .api_route("/projects/:project_id", put(handlers::update_project))
         .api_route("/projects/:project_id", delete(handlers::delete_project))
@@ -107,14 +178,93 @@ fn create_api_router() -> ApiRouter<AppState> {
             "/projects/:project_id/versions",
             get(handlers::list_versions),
         )
+        .api_route(
+            "/projects/:project_id/versions/:version_number",
+            get(handlers::get_version),
+        )
+        .api_route(
+            "/projects/:project_id/versions/:version_number/smoke-test",
+            post(handlers::smoke_test_version),
+        )
+        .api_route(
+            "/projects/:project_id/versions/:version_number/revert",
+            post(handlers::revert_version),
+        )
+        .api_route(
+            "/projects/:project_id/versions/:version_number/diff/:to_version",
+            get(handlers::diff_versions),
+        )
+        .api_route(
+            "/projects/:project_id/versions/:version_number/changelog/:to_version",
+            post(handlers::generate_version_changelog),
+        )
+        .api_route("/projects/:project_id/fork", post(handlers::fork_project))
+        .api_route(
+            "/projects/:project_id/export",
+            get(handlers::export_project),
+        )
+        .api_route(
+            "/projects/:project_id/branches",
+            get(handlers::list_branches),
+        )
+        .api_route(
+            "/projects/:project_id/branches",
+            post(handlers::create_branch),
+        )
+        .api_route(
+            "/projects/:project_id/branches/:branch/merge",
+            post(handlers::merge_branch),
+        )
         .api_route(
             "/projects/:project_id/release",
             post(handlers::release_version),
         )
+        // Streams server-sent events, which aide can't document
+        .route(
+            "/projects/:project_id/generate",
+            axum::routing::post(handlers::generate_project_version),
+        )
+        .api_route(
+            "/projects/:project_id/retention",
+            get(handlers::get_retention_policy),
+        )
+        .api_route(
+            "/projects/:project_id/retention",
+            put(handlers::update_retention_policy),
+        )
+        .api_route(
+            "/projects/:project_id/retention",
+            delete(handlers::delete_retention_policy),
+        )
+        .api_route(
+            "/projects/:project_id/retention/preview",
+            get(handlers::preview_pruning),
+        )
+        .api_route(
+            "/projects/:project_id/prune",
+            post(handlers::prune_versions),
+        )
+        .api_route(
+            "/projects/:project_id/releases",
+            get(handlers::list_releases),
+        )
+        .api_route(
+            "/projects/:project_id/releases",
+            post(handlers::create_release),
+        )
         .api_route(
             "/projects/:project_id/convert",
             post(handlers::convert_to_app),
         )
+        // Template endpoints
+        .api_route("/templates", get(handlers::list_templates))
+        .api_route("/templates", post(handlers::create_template))
+        .api_route("/templates/:template_id", get(handlers::get_template))
+        .api_route("/templates/:template_id", delete(handlers::delete_template))
+        // Content-addressed source endpoints
+        .api_route("/sources/validate", post(handlers::validate_app_source))
+        .api_route("/sources/preview", post(handlers::preview_app_source))
+        .api_route("/sources/:hash", get(handlers::get_source))
         // Published projects endpoint
         .api_route(
             "/published-projects",
@@ -134,6 +284,70 @@ fn create_api_router() -> ApiRouter<AppState> {
                 .description("Create a new application")
                 .tag("Apps")
         }))
+        .api_route(
+            "/apps/:app_id",
+            get_with(handlers::get_app, |op| {
+                op.summary("Get app")
+                    .description("Get a single application, including whether an update is available")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id",
+            put_with(handlers::update_app, |op| {
+                op.summary("Update app")
+                    .description("Update an application's name, description, icon, price or data collections")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id",
+            delete_with(handlers::delete_app, |op| {
+                op.summary("Delete app")
+                    .description("Delete an application; with remove_data=true its data collections are deleted too")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/install",
+            post_with(handlers::install_app, |op| {
+                op.summary("Install app")
+                    .description("Install an uninstalled application; apps declaring capabilities need consent: true, or the request fails with 428")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/tokens",
+            post_with(handlers::issue_app_token, |op| {
+                op.summary("Issue app token")
+                    .description("Issue a token for a launched instance of an installed app, to send as X-App-Token on its host API requests")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/permissions",
+            get_with(handlers::get_app_permissions, |op| {
+                op.summary("Get app permissions")
+                    .description("Get the capabilities an app declares and has been granted, to show before installing it")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/permissions",
+            post_with(handlers::grant_app_permissions, |op| {
+                op.summary("Grant app permissions")
+                    .description("Grant an installed app the capabilities its current release declares; requires consent: true")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/uninstall",
+            post_with(handlers::uninstall_app, |op| {
+                op.summary("Uninstall app")
+                    .description("Uninstall an application, optionally removing its data collections after a snapshot")
+                    .tag("Apps")
+            }),
+        )
         .api_route(
             "/apps/:app_id/source",
             put_with(handlers::update_app_source_code, |op| {
@@ -142,4 +356,28 @@ fn create_api_router() -> ApiRouter<AppState> {
                     .tag("Apps")
             }),
         )
+        .api_route(
+            "/apps/:app_id/upgrade",
+            post_with(handlers::upgrade_app, |op| {
+                op.summary("Upgrade app")
+                    .description("Switch an app to its project's latest release, keeping its data")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/downgrade",
+            post_with(handlers::downgrade_app, |op| {
+                op.summary("Downgrade app")
+                    .description("Switch an app back to its previous release, or to a given release_id, keeping its data")
+                    .tag("Apps")
+            }),
+        )
+        .api_route(
+            "/apps/:app_id/export.html",
+            get_with(handlers::export_app_html, |op| {
+                op.summary("Export app")
+                    .description("Download the app as one self-contained HTML file that runs offline, storing its data in the browser")
+                    .tag("Apps")
+            }),
+        )
 }
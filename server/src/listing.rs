@@ -0,0 +1,239 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection};
+
+use crate::database::Database;
+use crate::models::{Document, QueryResult, SortField, SortOrder};
+use crate::sources::hydrate_sources_in;
+
+/// Conditions on a document's JSON data, evaluated in SQL
+#[derive(Debug, Default)]
+pub struct DocumentFilter {
+    /// Case-insensitive substring of `data.name` or `data.description`
+    pub search: Option<String>,
+    /// `data.status` must equal this
+    pub status: Option<String>,
+    /// `data.status` must not be any of these
+    pub exclude_statuses: Vec<String>,
+    /// `data.installed` must match; apps without the flag count as installed
+    pub installed: Option<bool>,
+    /// `data.<path>` must equal the given text
+    pub equals: Vec<(&'static str, String)>,
+    /// `data.<path>` must be at least the given number
+    pub at_least: Vec<(&'static str, f64)>,
+    /// `data.<path>` must be at most the given number
+    pub at_most: Vec<(&'static str, f64)>,
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+}
+
+/// Ordering for filtered listings; newest first by default
+#[derive(Debug, Default)]
+pub struct DocumentSort {
+    pub field: Option<SortField>,
+    pub order: Option<SortOrder>,
+}
+
+/// Check a date query parameter, accepting RFC 3339 timestamps and plain `YYYY-MM-DD` dates
+pub fn parse_date_param(value: Option<String>) -> Result<Option<String>, String> {
+    match value {
+        None => Ok(None),
+        Some(value) => {
+            let valid = DateTime::parse_from_rfc3339(&value).is_ok()
+                || NaiveDate::parse_from_str(&value, "%Y-%m-%d").is_ok();
+            if valid {
+                Ok(Some(value))
+            } else {
+                Err(format!("invalid date: {}", value))
+            }
+        }
+    }
+}
+
+impl Database {
+    /// Like `list_documents`, but only documents matching `filter` are listed and counted
+    pub async fn list_filtered_documents(
+        &self,
+        collection: &str,
+        filter: &DocumentFilter,
+        sort: &DocumentSort,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<QueryResult, sqlx::Error> {
+        let limit = limit.unwrap_or(100).min(1000);
+        let offset = offset.unwrap_or(0);
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT id, collection, data, created_at, updated_at FROM documents",
+        );
+        push_filter(&mut query, collection, filter);
+        push_sort(&mut query, sort);
+        query.push(" LIMIT ");
+        query.push_bind(limit);
+        query.push(" OFFSET ");
+        query.push_bind(offset);
+
+        let mut documents: Vec<Document> = query
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(document_from_row)
+            .collect();
+        self.hydrate_sources(&mut documents).await?;
+
+        let mut count_query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) AS count FROM documents");
+        push_filter(&mut count_query, collection, filter);
+        let count_row = count_query.build().fetch_one(&self.pool).await?;
+
+        Ok(QueryResult {
+            documents,
+            count: count_row.get("count"),
+        })
+    }
+
+    /// Documents of a collection whose `data.<path>` is one of `values`
+    pub async fn list_documents_where_in(
+        &self,
+        collection: &str,
+        path: &str,
+        values: &[String],
+    ) -> Result<Vec<Document>, sqlx::Error> {
+        if values.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.pool.acquire().await?;
+        documents_where_in(&mut conn, collection, path, values).await
+    }
+}
+
+/// Documents of a collection whose `data.<path>` is one of `values`, read through `conn`,
+/// e.g. an open transaction
+pub(crate) async fn documents_where_in(
+    conn: &mut SqliteConnection,
+    collection: &str,
+    path: &str,
+    values: &[String],
+) -> Result<Vec<Document>, sqlx::Error> {
+    if values.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut query = QueryBuilder::<Sqlite>::new(
+        "SELECT id, collection, data, created_at, updated_at FROM documents WHERE collection = ",
+    );
+    query.push_bind(collection.to_string());
+    query.push(format!(" AND json_extract(data, '$.{}') IN (", path));
+    let mut separated = query.separated(", ");
+    for value in values {
+        separated.push_bind(value.clone());
+    }
+    separated.push_unseparated(") ORDER BY created_at");
+
+    let mut documents: Vec<Document> = query
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(document_from_row)
+        .collect();
+    hydrate_sources_in(conn, &mut documents).await?;
+    Ok(documents)
+}
+
+fn push_filter(query: &mut QueryBuilder<'_, Sqlite>, collection: &str, filter: &DocumentFilter) {
+    query.push(" WHERE collection = ");
+    query.push_bind(collection.to_string());
+
+    if let Some(search) = &filter.search {
+        // Escape LIKE wildcards so the search is a plain substring match
+        let pattern = format!(
+            "%{}%",
+            search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        query.push(" AND (json_extract(data, '$.name') LIKE ");
+        query.push_bind(pattern.clone());
+        query.push(" ESCAPE '\\' OR json_extract(data, '$.description') LIKE ");
+        query.push_bind(pattern);
+        query.push(" ESCAPE '\\')");
+    }
+
+    // Documents without a status are drafts
+    let status = "COALESCE(json_extract(data, '$.status'), 'draft')";
+    if let Some(wanted) = &filter.status {
+        query.push(format!(" AND {} = ", status));
+        query.push_bind(wanted.clone());
+    }
+    if !filter.exclude_statuses.is_empty() {
+        query.push(format!(" AND {} NOT IN (", status));
+        let mut separated = query.separated(", ");
+        for excluded in &filter.exclude_statuses {
+            separated.push_bind(excluded.clone());
+        }
+        separated.push_unseparated(")");
+    }
+
+    if let Some(installed) = filter.installed {
+        // json_extract reads both JSON booleans and the 1/0 older apps stored as integers
+        query.push(" AND COALESCE(json_extract(data, '$.installed'), 1) = ");
+        query.push_bind(installed as i32);
+    }
+    for (path, value) in &filter.equals {
+        query.push(format!(" AND CAST(json_extract(data, '$.{}') AS TEXT) = ", path));
+        query.push_bind(value.clone());
+    }
+    for (path, value) in &filter.at_least {
+        query.push(format!(" AND json_extract(data, '$.{}') >= ", path));
+        query.push_bind(*value);
+    }
+    for (path, value) in &filter.at_most {
+        query.push(format!(" AND json_extract(data, '$.{}') <= ", path));
+        query.push_bind(*value);
+    }
+
+    let date_bounds = [
+        ("created_at", ">=", &filter.created_after),
+        ("created_at", "<=", &filter.created_before),
+        ("updated_at", ">=", &filter.updated_after),
+        ("updated_at", "<=", &filter.updated_before),
+    ];
+    for (column, comparison, bound) in date_bounds {
+        if let Some(bound) = bound {
+            query.push(format!(" AND julianday({}) {} julianday(", column, comparison));
+            query.push_bind(bound.clone());
+            query.push(")");
+        }
+    }
+}
+
+fn push_sort(query: &mut QueryBuilder<'_, Sqlite>, sort: &DocumentSort) {
+    let column = match sort.field.unwrap_or(SortField::CreatedAt) {
+        SortField::CreatedAt => "created_at",
+        SortField::UpdatedAt => "updated_at",
+        SortField::Name => "json_extract(data, '$.name') COLLATE NOCASE",
+        SortField::Price => "json_extract(data, '$.price')",
+    };
+    let direction = match sort.order.unwrap_or(SortOrder::Desc) {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    // Ties fall back to newest first so pages are stable
+    query.push(format!(" ORDER BY {} {}, created_at DESC, id", column, direction));
+}
+
+pub(crate) fn document_from_row(row: &sqlx::sqlite::SqliteRow) -> Document {
+    let data: Value = serde_json::from_str(row.get("data")).unwrap();
+    Document {
+        id: row.get("id"),
+        collection: row.get("collection"),
+        data,
+        created_at: DateTime::parse_from_rfc3339(row.get("created_at"))
+            .unwrap()
+            .with_timezone(&Utc),
+        updated_at: DateTime::parse_from_rfc3339(row.get("updated_at"))
+            .unwrap()
+            .with_timezone(&Utc),
+    }
+}
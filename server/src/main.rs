@@ -16,6 +16,9 @@ async fn main() {
 
     tracing::info!("Database initialized at: {}", database_url);
 
+    mini_server::retention::spawn_pruning_job(database.clone());
+    mini_server::runtime::preload_react_bundle();
+
     let port = env::var("PORT").unwrap_or_else(|_| "10000".to_string());
     let addr = format!("0.0.0.0:{}", port);
 
@@ -0,0 +1,131 @@
+use std::time::Instant;
+
+use sqlx::Row;
+
+use crate::database::Database;
+use crate::models::{DatabaseStats, IntegrityReport, MaintenanceReport, SourceCollectionReport};
+
+impl Database {
+    /// Report page usage and on-disk size of the main database file
+    pub async fn database_stats(&self) -> Result<DatabaseStats, sqlx::Error> {
+        let page_size: i64 = sqlx::query("PRAGMA page_size")
+            .fetch_one(&self.pool)
+            .await?
+            .get(0);
+        let page_count: i64 = sqlx::query("PRAGMA page_count")
+            .fetch_one(&self.pool)
+            .await?
+            .get(0);
+        let freelist_count: i64 = sqlx::query("PRAGMA freelist_count")
+            .fetch_one(&self.pool)
+            .await?
+            .get(0);
+        let journal_mode: String = sqlx::query("PRAGMA journal_mode")
+            .fetch_one(&self.pool)
+            .await?
+            .get(0);
+
+        // Empty for in-memory databases
+        let path: String = sqlx::query("PRAGMA database_list")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .find(|row| row.get::<String, _>("name") == "main")
+            .map(|row| row.get("file"))
+            .unwrap_or_default();
+
+        let file_size_bytes = file_size(&path);
+        let wal_size_bytes = file_size(&format!("{}-wal", path));
+
+        Ok(DatabaseStats {
+            file_size_bytes,
+            wal_size_bytes,
+            page_size,
+            page_count,
+            freelist_count,
+            journal_mode,
+        })
+    }
+
+    /// Rebuild the database file to reclaim space left by deleted documents
+    pub async fn vacuum(&self) -> Result<MaintenanceReport, sqlx::Error> {
+        let before = self.database_stats().await?;
+        let started = Instant::now();
+
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        // In WAL mode the rebuilt pages land in the WAL; fold them back into the main file
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+
+        let duration_ms = started.elapsed().as_millis() as i64;
+        let after = self.database_stats().await?;
+
+        Ok(MaintenanceReport {
+            operation: "vacuum".to_string(),
+            duration_ms,
+            before,
+            after,
+        })
+    }
+
+    /// Delete sources no document or snapshot refers to any more, such as those of deleted
+    /// versions and apps
+    pub async fn collect_sources(&self) -> Result<SourceCollectionReport, sqlx::Error> {
+        let deleted = self.collect_unreferenced_sources().await?;
+        Ok(SourceCollectionReport { deleted })
+    }
+
+    /// Refresh the statistics the query planner uses to pick indexes
+    pub async fn analyze(&self) -> Result<MaintenanceReport, sqlx::Error> {
+        let before = self.database_stats().await?;
+        let started = Instant::now();
+
+        sqlx::query("ANALYZE").execute(&self.pool).await?;
+
+        let duration_ms = started.elapsed().as_millis() as i64;
+        let after = self.database_stats().await?;
+
+        Ok(MaintenanceReport {
+            operation: "analyze".to_string(),
+            duration_ms,
+            before,
+            after,
+        })
+    }
+
+    /// Run `PRAGMA integrity_check` (or the faster `quick_check`)
+    pub async fn integrity_check(&self, quick: bool) -> Result<IntegrityReport, sqlx::Error> {
+        let pragma = if quick {
+            "PRAGMA quick_check"
+        } else {
+            "PRAGMA integrity_check"
+        };
+
+        let messages: Vec<String> = sqlx::query(pragma)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        let foreign_key_violations = sqlx::query("PRAGMA foreign_key_check")
+            .fetch_all(&self.pool)
+            .await?
+            .len() as i64;
+
+        Ok(IntegrityReport {
+            ok: messages.len() == 1 && messages[0] == "ok" && foreign_key_violations == 0,
+            quick,
+            messages,
+            foreign_key_violations,
+        })
+    }
+}
+
+fn file_size(path: &str) -> Option<u64> {
+    if path.is_empty() {
+        return None;
+    }
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
@@ -2,6 +2,76 @@ use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::capabilities::declared_manifest;
+use crate::templates::BuiltinTemplate;
+
+/// Branch every project starts with
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// Branch heads of a project document; projects created before branching have only `main`
+pub fn project_branches(data: &Value) -> BTreeMap<String, i32> {
+    let mut branches: BTreeMap<String, i32> = data
+        .get("branches")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    if branches.is_empty() {
+        let current_version = data.get("current_version").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+        branches.insert(DEFAULT_BRANCH.to_string(), current_version);
+    }
+    branches
+}
+
+/// Lifecycle status of a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectStatus {
+    Draft,
+    InReview,
+    Published,
+    Archived,
+    Deprecated,
+}
+
+impl ProjectStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectStatus::Draft => "draft",
+            ProjectStatus::InReview => "in_review",
+            ProjectStatus::Published => "published",
+            ProjectStatus::Archived => "archived",
+            ProjectStatus::Deprecated => "deprecated",
+        }
+    }
+
+    /// Status stored on a project document; anything unrecognized counts as a draft
+    pub fn from_data(data: &Value) -> Self {
+        data.get("status")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or(ProjectStatus::Draft)
+    }
+
+    /// Whether a project may move from this status to `next`
+    pub fn can_transition_to(&self, next: ProjectStatus) -> bool {
+        use ProjectStatus::*;
+        matches!(
+            (self, next),
+            (Draft, InReview | Published | Archived)
+                | (InReview, Draft | Published | Archived)
+                | (Published, Draft | Deprecated | Archived)
+                | (Deprecated, Published | Archived)
+                | (Archived, Draft)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectStatusChange {
+    pub from: ProjectStatus,
+    pub to: ProjectStatus,
+    pub changed_at: DateTime<Utc>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Document {
@@ -37,11 +107,145 @@ pub struct ListQuery {
     pub offset: Option<i64>,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    CreatedAt,
+    UpdatedAt,
+    Name,
+    Price,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListProjectsQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// Only list projects with this status
+    pub status: Option<ProjectStatus>,
+    /// Include archived projects, which are hidden by default
+    pub include_archived: Option<bool>,
+    /// Search name and description
+    pub q: Option<String>,
+    /// Model the project was created with
+    pub model: Option<String>,
+    /// RFC 3339 timestamp or `YYYY-MM-DD`
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+    pub sort: Option<SortField>,
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListAppsQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// Search name and description
+    pub q: Option<String>,
+    pub status: Option<String>,
+    pub installed: Option<bool>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub model: Option<String>,
+    /// RFC 3339 timestamp or `YYYY-MM-DD`
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+    pub sort: Option<SortField>,
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SchemaQuery {
+    /// Number of most recent documents to sample (default 100, at least 1)
+    pub sample: Option<i64>,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct QueryRequest {
     pub query: String,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PrepareResetRequest {
+    /// Collections to reset; omit to reset the whole database
+    pub collections: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ResetDatabaseRequest {
+    /// Confirmation token returned by `POST /api/db/reset/prepare`
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ResetToken {
+    pub token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+    pub document_count: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Snapshot {
+    pub id: String,
+    pub reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+    pub document_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct IntegrityQuery {
+    /// Run the faster `quick_check` instead of a full `integrity_check`
+    pub quick: Option<bool>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DatabaseStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wal_size_bytes: Option<u64>,
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    pub journal_mode: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct MaintenanceReport {
+    pub operation: String,
+    pub duration_ms: i64,
+    pub before: DatabaseStats,
+    pub after: DatabaseStats,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SourceCollectionReport {
+    /// Sources deleted because no document or snapshot referred to them
+    pub deleted: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct IntegrityReport {
+    pub ok: bool,
+    pub quick: bool,
+    pub messages: Vec<String>,
+    pub foreign_key_violations: i64,
+}
+
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct ApiResponse<T> where T: JsonSchema {
     pub data: T,
@@ -80,7 +284,15 @@ fn json_value_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::sch
 impl From<Document> for App {
     fn from(doc: Document) -> Self {
         let data = &doc.data;
-        App {
+        // Stored as 1/0 by older apps, so accept either form
+        let installed = data.get("installed")
+            .and_then(|v| v.as_bool().or_else(|| v.as_i64().map(|i| i != 0)))
+            .unwrap_or(true);
+        let manifest: Option<AppManifest> = data.get("manifest")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+        let granted: Option<AppManifest> = data.get("granted")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+        let mut app = App {
             id: data.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
             name: data.get("name").and_then(|v| v.as_str()).unwrap_or("Untitled App").to_string(),
             description: data.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string(),
@@ -93,19 +305,42 @@ impl From<Document> for App {
                     .unwrap_or_else(|| "1".to_string())),
             price: data.get("price").and_then(|v| v.as_f64()).unwrap_or(0.0),
             icon: data.get("icon").and_then(|v| v.as_str()).unwrap_or("📱").to_string(),
-            installed: data.get("installed").and_then(|v| v.as_i64()).unwrap_or(1) as i32,
+            installed,
+            data_collections: data.get("data_collections")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default(),
             source_code: data.get("source_code").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            source_hash: data.get("source_hash").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            validation: data.get("validation").and_then(|v| serde_json::from_value(v.clone()).ok()),
             prompt: data.get("prompt").and_then(|v| v.as_str()).map(|s| s.to_string()),
             model: data.get("model").and_then(|v| v.as_str()).map(|s| s.to_string()),
             status: data.get("status").and_then(|v| v.as_str()).unwrap_or("draft").to_string(),
             project_id: data.get("project_id").and_then(|v| v.as_str()).map(|s| s.to_string()),
             project_version: data.get("project_version").and_then(|v| v.as_i64()).map(|i| i as i32),
+            release_id: data.get("release_id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            previous_release: data.get("previous_release")
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            update_notice: data.get("update_notice")
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            update_available: false,
+            latest_version: None,
+            manifest,
+            granted,
+            granted_at: data.get("granted_at")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc)),
+            consent_required: false,
             created_at: data.get("created_at")
                 .and_then(|v| v.as_str())
                 .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
                 .map(|dt| dt.with_timezone(&Utc))
                 .unwrap_or(doc.created_at),
-        }
+        };
+        // Nothing is allowed until granted, so anything declared beyond the grant needs consent
+        app.consent_required = installed
+            && !app.granted.clone().unwrap_or_default().covers(&declared_manifest(&app));
+        app
     }
 }
 
@@ -117,10 +352,17 @@ impl From<Document> for Project {
             name: data.get("name").and_then(|v| v.as_str()).unwrap_or("Untitled Project").to_string(),
             description: data.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string(),
             icon: data.get("icon").and_then(|v| v.as_str()).unwrap_or("📋").to_string(),
-            status: data.get("status").and_then(|v| v.as_str()).unwrap_or("draft").to_string(),
+            status: ProjectStatus::from_data(data),
+            status_history: data.get("status_history")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default(),
             current_version: data.get("current_version").and_then(|v| v.as_i64()).unwrap_or(0) as i32,
             initial_prompt: data.get("initial_prompt").and_then(|v| v.as_str()).unwrap_or("").to_string(),
             initial_model: data.get("initial_model").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            branches: project_branches(data),
+            forked_from: data.get("forked_from").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            template_id: data.get("template_id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            retention: data.get("retention").and_then(|v| serde_json::from_value(v.clone()).ok()),
             created_at: data.get("created_at")
                 .and_then(|v| v.as_str())
                 .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
@@ -145,7 +387,42 @@ impl From<Document> for ProjectVersion {
             version_number: data.get("version_number").and_then(|v| v.as_i64()).unwrap_or(0) as i32,
             prompt: data.get("prompt").and_then(|v| v.as_str()).unwrap_or("").to_string(),
             source_code: data.get("source_code").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            source_hash: data.get("source_hash").and_then(|v| v.as_str()).unwrap_or("").to_string(),
             model: data.get("model").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            reverted_from: data.get("reverted_from").and_then(|v| v.as_i64()).map(|i| i as i32),
+            branch: data.get("branch").and_then(|v| v.as_str()).unwrap_or(DEFAULT_BRANCH).to_string(),
+            parent_version: data.get("parent_version").and_then(|v| v.as_i64()).map(|i| i as i32),
+            merged_from: data.get("merged_from").and_then(|v| v.as_i64()).map(|i| i as i32),
+            usage: data.get("usage").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            changelog: data.get("changelog").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            validation: data.get("validation").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            health: data.get("health").and_then(|v| serde_json::from_value(v.clone()).ok()),
+            created_at: data.get("created_at")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or(doc.created_at),
+        }
+    }
+}
+
+impl From<Document> for Release {
+    fn from(doc: Document) -> Self {
+        let data = &doc.data;
+        Release {
+            id: data.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            project_id: data.get("project_id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            project_version: data.get("project_version").and_then(|v| v.as_i64()).unwrap_or(0) as i32,
+            version: data.get("version").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            tags: data.get("tags")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default(),
+            notes: data.get("notes").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            app_id: data.get("app_id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            price: data.get("price").and_then(|v| v.as_f64()).unwrap_or(0.0),
+            previous_release_id: data.get("previous_release_id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            changelog: data.get("changelog").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            manifest: data.get("manifest").and_then(|v| serde_json::from_value(v.clone()).ok()),
             created_at: data.get("created_at")
                 .and_then(|v| v.as_str())
                 .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
@@ -155,6 +432,35 @@ impl From<Document> for ProjectVersion {
     }
 }
 
+impl From<Document> for Template {
+    fn from(doc: Document) -> Self {
+        let data = &doc.data;
+        Template {
+            id: data.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            name: data.get("name").and_then(|v| v.as_str()).unwrap_or("Untitled Template").to_string(),
+            description: data.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            icon: data.get("icon").and_then(|v| v.as_str()).unwrap_or("📋").to_string(),
+            builtin: false,
+            source_code: data.get("source_code").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            created_at: Some(doc.created_at),
+        }
+    }
+}
+
+impl From<&BuiltinTemplate> for Template {
+    fn from(template: &BuiltinTemplate) -> Self {
+        Template {
+            id: template.id.to_string(),
+            name: template.name.to_string(),
+            description: template.description.to_string(),
+            icon: template.icon.to_string(),
+            builtin: true,
+            source_code: Some(template.source_code.to_string()),
+            created_at: None,
+        }
+    }
+}
+
 impl From<Document> for DashboardLayout {
     fn from(doc: Document) -> Self {
         let data = &doc.data;
@@ -181,17 +487,35 @@ pub struct Project {
     pub name: String,
     pub description: String,
     pub icon: String,
-    pub status: String,
+    pub status: ProjectStatus,
+    /// Every status change, oldest first
+    pub status_history: Vec<ProjectStatusChange>,
     pub current_version: i32,
     pub initial_prompt: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub initial_model: Option<String>,
+    /// Head version number of each branch
+    pub branches: BTreeMap<String, i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<ForkOrigin>,
+    /// Template the project was created from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+    /// Which old versions pruning may delete; without one every version is kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub versions: Option<Vec<ProjectVersion>>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ForkOrigin {
+    pub project_id: String,
+    pub version_number: i32,
+}
+
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectVersion {
     pub id: String,
@@ -199,11 +523,125 @@ pub struct ProjectVersion {
     pub version_number: i32,
     pub prompt: String,
     pub source_code: String,
+    /// SHA-256 of `source_code`, hex encoded
+    pub source_hash: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Version number this version was reverted from, if it is a revert
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverted_from: Option<i32>,
+    pub branch: String,
+    /// Previous head of the branch this version was appended to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_version: Option<i32>,
+    /// Head version of the branch merged into this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged_from: Option<i32>,
+    /// Tokens spent generating this version, if it was generated server-side
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Summary of what changed since an earlier version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<VersionChangelog>,
+    /// Diagnostics for `source_code`, recorded when the version was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation: Option<SourceValidation>,
+    /// Outcome of running `source_code` headlessly, for versions that passed validation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<AppHealth>,
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct VersionChangelog {
+    pub text: String,
+    /// Version the changes are relative to
+    pub from_version: i32,
+    pub model: String,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Result of statically checking app source code
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SourceValidation {
+    /// Whether the source has no errors; warnings don't count
+    pub valid: bool,
+    pub diagnostics: Vec<SourceDiagnostic>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SourceDiagnostic {
+    pub severity: DiagnosticSeverity,
+    /// Which check found the problem, e.g. `syntax`, `typescript` or `eval`
+    pub code: String,
+    pub message: String,
+    /// 1-based line the problem starts on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// 1-based column the problem starts at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticSeverity {
+    /// Blocks releasing the source
+    Error,
+    Warning,
+}
+
+/// Outcome of evaluating app source in a headless JavaScript engine and rendering it once
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AppHealth {
+    pub status: HealthStatus,
+    /// Step that failed: `evaluate`, `component`, `render` or `timeout`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub duration_ms: u64,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// Returned a function component whose first render didn't throw
+    Healthy,
+    Failing,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ValidateSourceRequest {
+    pub source_code: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PreviewSourceRequest {
+    pub source_code: String,
+    /// Title of the preview page (default "Preview")
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SourceValidationResponse {
+    pub data: SourceValidation,
+    pub links: SourceValidationLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SourceValidationLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TokenUsage {
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+}
+
 // Project-related requests
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CreateProjectRequest {
@@ -211,12 +649,87 @@ pub struct CreateProjectRequest {
     pub model: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GenerateVersionRequest {
+    pub prompt: String,
+    pub model: Option<String>,
+    /// Branch to generate on; its head version's code is modified if it has one
+    pub branch: Option<String>,
+}
+
+/// Per-project rules for which versions survive pruning.
+///
+/// A version is kept if any rule keeps it. Branch heads and the versions an app is running
+/// or can downgrade to are always kept.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RetentionPolicy {
+    /// Keep this many of the newest versions
+    pub keep_last: Option<u32>,
+    /// Keep every version that has a release record
+    #[serde(default = "default_keep_released")]
+    pub keep_released: bool,
+    /// Keep the newest version of each day (UTC)
+    #[serde(default)]
+    pub keep_daily: bool,
+}
+
+fn default_keep_released() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RetentionPolicyResponse {
+    pub data: Option<RetentionPolicy>,
+    pub links: RetentionPolicyLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RetentionPolicyLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub project: String,
+    pub preview: String,
+}
+
+/// What pruning a project did, or would do in a dry run
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PruneReport {
+    pub project_id: String,
+    pub dry_run: bool,
+    pub kept: Vec<RetainedVersion>,
+    /// Version numbers removed (or that would be)
+    pub removed: Vec<i32>,
+    /// Source code bytes freed by the removed versions
+    pub bytes_freed: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RetainedVersion {
+    pub version_number: i32,
+    /// Why the version is kept: `branch_head`, `app_current`, `app_previous`, `released`,
+    /// `recent`, `daily` or `no_policy`
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PruneReportResponse {
+    pub data: PruneReport,
+    pub links: PruneReportLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PruneReportLinks {
+    pub project: String,
+    pub versions: String,
+    pub retention: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateProjectRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub icon: Option<String>,
-    pub status: Option<String>,
+    pub status: Option<ProjectStatus>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -224,12 +737,38 @@ pub struct CreateVersionRequest {
     pub prompt: String,
     pub source_code: String,
     pub model: Option<String>,
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ForkProjectRequest {
+    /// Version to seed the fork from (default: the project's current version)
+    pub version_number: Option<i32>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateBranchRequest {
+    pub name: String,
+    /// Version the branch starts from (default: head of "main")
+    pub from_version: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MergeBranchRequest {
+    /// Branch to merge into (default "main")
+    pub into: Option<String>,
+    /// Which head becomes the merge result: either branch head (default: the merged branch's)
+    pub head: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ReleaseVersionRequest {
     pub version_number: i32,
     pub price: Option<f64>,
+    pub version: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -238,6 +777,78 @@ pub struct ConvertToAppRequest {
     pub price: Option<f64>,
 }
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ImportProjectQuery {
+    /// `new` (default) recreates the project under new ids; `merge` appends its versions to `into`
+    pub mode: Option<String>,
+    /// Project to merge into
+    pub into: Option<String>,
+    /// Branch of `into` that merged versions are appended to
+    pub branch: Option<String>,
+}
+
+// Template domain models
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub icon: String,
+    /// Built-in templates ship with the server and can't be deleted
+    pub builtin: bool,
+    /// Only included when fetching a single template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateTemplateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    /// Template source; alternatively copy it from `project_id`'s `version_number`
+    pub source_code: Option<String>,
+    pub project_id: Option<String>,
+    pub version_number: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateFromTemplateRequest {
+    /// Defaults to the template's name
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+}
+
+// Release domain models
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Release {
+    pub id: String,
+    pub project_id: String,
+    /// Project version number that was released
+    pub project_version: i32,
+    /// Semantic version of the release, e.g. "1.2.3"
+    pub version: String,
+    pub tags: Vec<String>,
+    pub notes: String,
+    /// App the release was published to; absent for releases imported from another node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    pub price: f64,
+    /// Release that was live on the app before this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_release_id: Option<String>,
+    /// Generated summary of what changed in the released version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<String>,
+    /// Capabilities the released app needs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<AppManifest>,
+    pub created_at: DateTime<Utc>,
+}
+
 // App domain models
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct App {
@@ -247,9 +858,17 @@ pub struct App {
     pub version: String,
     pub price: f64,
     pub icon: String,
-    pub installed: i32,
+    pub installed: bool,
+    /// Collections holding the app's data, removed on request when it is uninstalled
+    pub data_collections: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source_code: Option<String>,
+    /// SHA-256 of `source_code`, hex encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+    /// Diagnostics for `source_code`, recorded when it was last set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation: Option<SourceValidation>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prompt: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -259,9 +878,93 @@ pub struct App {
     pub project_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_version: Option<i32>,
+    /// Release currently published to this app
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_id: Option<String>,
+    /// Release this one replaced, kept for rollback
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_release: Option<AppReleaseRef>,
+    /// Set when a new release replaced the source of an installed app
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_notice: Option<AppUpdateNotice>,
+    /// Whether the project's latest release differs from what this installed app runs
+    #[serde(default)]
+    pub update_available: bool,
+    /// Semver of the project's latest release
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_version: Option<String>,
+    /// Capabilities the app's release declares
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<AppManifest>,
+    /// Capabilities the user agreed to when installing the app
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub granted: Option<AppManifest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub granted_at: Option<DateTime<Utc>>,
+    /// Whether the declared capabilities go beyond what the user granted
+    #[serde(default)]
+    pub consent_required: bool,
     pub created_at: DateTime<Utc>,
 }
 
+/// What an app may do through the host API, declared by its release and granted by the
+/// user when the app is installed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AppManifest {
+    /// Collections the app uses; `"*"` stands for every collection except the server's own
+    #[serde(default)]
+    pub collections: Vec<CollectionCapability>,
+    /// Limit on the data the app keeps in the collections it can write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageCapability>,
+    /// Whether the app takes payments
+    #[serde(default)]
+    pub payments: bool,
+    /// Hosts the app connects to directly, e.g. `api.example.com`
+    #[serde(default)]
+    pub network: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CollectionCapability {
+    pub name: String,
+    #[serde(default)]
+    pub access: CollectionAccess,
+    /// Use the collection other apps share instead of one private to this app
+    #[serde(default)]
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectionAccess {
+    Read,
+    #[default]
+    ReadWrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StorageCapability {
+    /// Most bytes of document data the app may store
+    pub max_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AppReleaseRef {
+    pub release_id: Option<String>,
+    pub version: String,
+    pub project_version: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AppUpdateNotice {
+    pub from_version: Option<String>,
+    pub to_version: String,
+    pub release_id: String,
+    pub notes: String,
+    pub released_at: DateTime<Utc>,
+}
+
 // App-related requests
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CreateAppRequest {
@@ -280,6 +983,35 @@ pub struct UpdateAppSourceCodeRequest {
     pub source_code: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct UpdateAppRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub price: Option<f64>,
+    pub data_collections: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RemoveAppDataOptions {
+    /// Also delete the app's `data_collections`; a snapshot is taken first
+    #[serde(default)]
+    pub remove_data: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ConsentRequest {
+    /// The user agreed to the app's declared capabilities
+    #[serde(default)]
+    pub consent: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DowngradeAppRequest {
+    /// Release to go back to; defaults to the app's previous release
+    pub release_id: Option<String>,
+}
+
 // Dashboard domain models
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct DashboardLayout {
@@ -371,6 +1103,8 @@ pub struct AppListMeta {
     pub count: i64,
     pub limit: i64,
     pub offset: i64,
+    /// Number of installed apps in this page with a newer release available
+    pub updates_available: usize,
 }
 
 #[derive(Debug, Serialize, JsonSchema)]
@@ -392,6 +1126,65 @@ pub struct AppResponseLinks {
     pub self_link: String,
 }
 
+/// Token identifying one launched instance of an app on its host API requests
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppToken {
+    pub token: String,
+    pub app_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppTokenResponse {
+    pub data: AppToken,
+    pub links: AppResponseLinks,
+}
+
+/// An app's capabilities as shown to the user before installing it
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppPermissions {
+    pub app_id: String,
+    /// Capabilities the app asks for
+    pub manifest: AppManifest,
+    /// Capabilities the user agreed to
+    pub granted: Option<AppManifest>,
+    /// Capabilities enforced on the app's host API requests right now
+    pub effective: AppManifest,
+    pub consent_required: bool,
+    /// One line per capability the app asks for, for the consent prompt
+    pub summary: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppPermissionsResponse {
+    pub data: AppPermissions,
+    pub links: AppPermissionsLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppPermissionsLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub app: String,
+    pub install: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppUninstallResponse {
+    pub data: App,
+    pub meta: AppUninstallMeta,
+    pub links: AppResponseLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AppUninstallMeta {
+    /// Data collections that were deleted
+    pub removed_collections: Vec<String>,
+    /// Snapshot taken before deleting them, which can restore the data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<String>,
+}
+
 // Project response types
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct ProjectListResponse {
@@ -462,6 +1255,196 @@ pub struct ProjectVersionResponseLinks {
     pub project: String,
 }
 
+// Project Version diff types
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DiffQuery {
+    /// Unchanged lines to show around each change (default 3)
+    pub context: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ChangelogRequest {
+    /// Model to write the changelog with (defaults to the server default)
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectVersionChangelog {
+    pub project_id: String,
+    pub to_version: i32,
+    pub changelog: VersionChangelog,
+    /// Releases of the later version that the changelog was copied onto
+    pub release_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectVersionChangelogResponse {
+    pub data: ProjectVersionChangelog,
+    pub links: ProjectVersionChangelogLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectVersionChangelogLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub version: String,
+    pub diff: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectVersionDiff {
+    pub project_id: String,
+    pub from_version: i32,
+    pub to_version: i32,
+    pub unified: String,
+    pub hunks: Vec<DiffHunk>,
+    pub stats: DiffStats,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DiffHunk {
+    pub header: String,
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DiffLine {
+    /// One of "context", "insert" or "delete"
+    pub kind: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<usize>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DiffStats {
+    pub insertions: usize,
+    pub deletions: usize,
+    pub unchanged: usize,
+    pub hunks: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectVersionDiffResponse {
+    pub data: ProjectVersionDiff,
+    pub links: ProjectVersionDiffLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectVersionDiffLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub from: String,
+    pub to: String,
+    pub project: String,
+}
+
+// Project branch response types
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectBranch {
+    pub name: String,
+    pub head_version: i32,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectBranchListResponse {
+    pub data: Vec<ProjectBranch>,
+    pub links: ProjectBranchListLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectBranchListLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub project: String,
+}
+
+// Project import/export response types
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectImport {
+    pub project_id: String,
+    pub mode: String,
+    /// Id of the project on the node it was exported from
+    pub source_project_id: String,
+    pub versions_imported: usize,
+    /// Versions not merged because the target already has identical source code
+    pub versions_skipped: usize,
+    pub releases_imported: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectImportResponse {
+    pub data: ProjectImport,
+    pub links: ProjectImportLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProjectImportLinks {
+    pub project: String,
+    pub versions: String,
+}
+
+// Template response types
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TemplateResponse {
+    pub data: Template,
+    pub links: TemplateResponseLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TemplateResponseLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub create_project: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TemplateListResponse {
+    pub data: Vec<Template>,
+    pub links: TemplateListLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TemplateListLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+}
+
+// Release response types
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReleaseResponse {
+    pub data: Release,
+    pub links: ReleaseResponseLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReleaseResponseLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub project: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReleaseListResponse {
+    pub data: Vec<Release>,
+    pub links: ReleaseListLinks,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReleaseListLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub project: String,
+}
+
 // Dashboard response types
 #[derive(Debug, Serialize, JsonSchema)]
 pub struct DashboardLayoutResponse {
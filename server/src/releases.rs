@@ -0,0 +1,204 @@
+use chrono::Utc;
+use semver::Version;
+use serde_json::{json, Value};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::database::Database;
+use crate::listing::document_from_row;
+use crate::models::{App, Document};
+use crate::sources::{hydrate_sources_in, save_source, split_source};
+
+impl Database {
+    /// Record a release of a project under the semver `choose_version` picks, given the
+    /// versions the project has already released, and publish it to the project's app.
+    ///
+    /// Reading the released versions and the app, inserting the release and creating or
+    /// updating the app all happen in one write transaction, so concurrent releases can
+    /// neither claim the same version, go backwards, nor create a second app. `app` holds
+    /// every field of a new app; an existing one only takes its name, description and icon,
+    /// plus an `update_notice` when installed. The release's `app_id` and
+    /// `previous_release_id` come from the app, as does its `price` when left null. Returns
+    /// `choose_version`'s error without recording anything when it refuses.
+    pub async fn insert_release<E>(
+        &self,
+        project_id: &str,
+        mut fields: Value,
+        mut app: Value,
+        choose_version: impl FnOnce(&[Version]) -> Result<Version, E>,
+    ) -> Result<Result<(Document, Document), E>, sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        // Writing first takes SQLite's write lock before anything is read, so a concurrent
+        // release waits here instead of reading the same versions
+        sqlx::query(
+            r#"
+            UPDATE documents
+            SET updated_at = ?
+            WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+            "#,
+        )
+        .bind(now.to_rfc3339())
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let released: Vec<Version> = sqlx::query(
+            r#"
+            SELECT json_extract(data, '$.version') AS version
+            FROM documents
+            WHERE collection = 'releases' AND json_extract(data, '$.project_id') = ?
+            "#,
+        )
+        .bind(project_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .filter_map(|row| row.get::<Option<String>, _>("version"))
+        .filter_map(|version| Version::parse(&version).ok())
+        .collect();
+
+        let version = match choose_version(&released) {
+            Ok(version) => version,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let existing: Option<Document> = sqlx::query(
+            r#"
+            SELECT id, collection, data, created_at, updated_at
+            FROM documents
+            WHERE collection = 'apps' AND json_extract(data, '$.project_id') = ?
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(project_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|row| document_from_row(&row));
+        let current: Option<App> = existing.clone().map(|doc| doc.into());
+
+        let id = Uuid::new_v4().to_string();
+        let app_id = match &current {
+            Some(current) => current.id.clone(),
+            None => app
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+        };
+        if let Some(data_obj) = fields.as_object_mut() {
+            data_obj.insert("version".to_string(), Value::String(version.to_string()));
+            data_obj.insert("app_id".to_string(), Value::String(app_id.clone()));
+            data_obj.insert(
+                "previous_release_id".to_string(),
+                json!(current.as_ref().and_then(|app| app.release_id.clone())),
+            );
+            if data_obj.get("price").is_none_or(Value::is_null) {
+                data_obj.insert(
+                    "price".to_string(),
+                    json!(current.as_ref().map(|app| app.price).unwrap_or(0.0)),
+                );
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO documents (id, collection, data, created_at, updated_at)
+            VALUES (?, 'releases', ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(serde_json::to_string(&fields).unwrap())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        let app_doc = match (existing, current) {
+            (Some(mut app_doc), Some(current)) => {
+                if let Some(data_obj) = app_doc.data.as_object_mut() {
+                    for key in ["name", "description", "icon"] {
+                        if let Some(value) = app.get(key) {
+                            data_obj.insert(key.to_string(), value.clone());
+                        }
+                    }
+                    // Installed copies keep running their release; the notice tells the user
+                    // an upgrade is waiting, and installing an uninstalled copy picks up the
+                    // latest release anyway
+                    if current.installed {
+                        data_obj.insert(
+                            "update_notice".to_string(),
+                            json!({
+                                "from_version": current.version,
+                                "to_version": version.to_string(),
+                                "release_id": fields.get("id"),
+                                "notes": fields.get("notes"),
+                                "released_at": now.to_rfc3339()
+                            }),
+                        );
+                    }
+                    data_obj.insert("updated_at".to_string(), json!(now.to_rfc3339()));
+                }
+
+                // The write lock has been held since the project update, so nothing else
+                // changed the app between reading and writing it
+                sqlx::query("UPDATE documents SET data = ?, updated_at = ? WHERE id = ?")
+                    .bind(serde_json::to_string(&app_doc.data).unwrap())
+                    .bind(now.to_rfc3339())
+                    .bind(&app_doc.id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                app_doc.updated_at = now;
+                hydrate_sources_in(&mut tx, std::slice::from_mut(&mut app_doc)).await?;
+                app_doc
+            }
+            _ => {
+                if let Some(data_obj) = app.as_object_mut() {
+                    data_obj.insert("id".to_string(), Value::String(app_id));
+                    data_obj.insert("version".to_string(), Value::String(version.to_string()));
+                    data_obj.insert("price".to_string(), fields["price"].clone());
+                }
+                let app_doc_id = Uuid::new_v4().to_string();
+                let (stored, source) = split_source("apps", &mut app);
+                if let Some((hash, source_code)) = source {
+                    save_source(&mut tx, &hash, &source_code).await?;
+                }
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO documents (id, collection, data, created_at, updated_at)
+                    VALUES (?, 'apps', ?, ?, ?)
+                    "#,
+                )
+                .bind(&app_doc_id)
+                .bind(serde_json::to_string(&stored).unwrap())
+                .bind(now.to_rfc3339())
+                .bind(now.to_rfc3339())
+                .execute(&mut *tx)
+                .await?;
+
+                Document {
+                    id: app_doc_id,
+                    collection: "apps".to_string(),
+                    data: app,
+                    created_at: now,
+                    updated_at: now,
+                }
+            }
+        };
+
+        tx.commit().await?;
+
+        let release_doc = Document {
+            id,
+            collection: "releases".to_string(),
+            data: fields,
+            created_at: now,
+            updated_at: now,
+        };
+        Ok(Ok((app_doc, release_doc)))
+    }
+}
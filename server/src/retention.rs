@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::listing::documents_where_in;
+use crate::models::{
+    project_branches, Document, PruneReport, RetainedVersion, RetentionPolicy,
+};
+
+/// Decide which of a project's versions a policy keeps.
+///
+/// `protected` maps version numbers that must survive regardless of the policy to the
+/// reason they're protected.
+pub fn plan_retention(
+    project_id: &str,
+    policy: Option<&RetentionPolicy>,
+    versions: &[Document],
+    released: &HashSet<i32>,
+    protected: &BTreeMap<i32, Vec<&'static str>>,
+) -> PruneReport {
+    // Newest first, so "keep last" and "one per day" both pick the latest versions
+    let mut ordered: Vec<(i32, &Document)> = versions
+        .iter()
+        .map(|doc| (version_number(doc), doc))
+        .collect();
+    ordered.sort_by_key(|(number, _)| std::cmp::Reverse(*number));
+
+    let mut days_seen = HashSet::new();
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+    let mut bytes_freed = 0;
+
+    for (index, (number, doc)) in ordered.iter().enumerate() {
+        let mut reasons: Vec<String> = protected
+            .get(number)
+            .map(|reasons| reasons.iter().map(|r| r.to_string()).collect())
+            .unwrap_or_default();
+
+        match policy {
+            None => reasons.push("no_policy".to_string()),
+            Some(policy) => {
+                if policy.keep_released && released.contains(number) {
+                    reasons.push("released".to_string());
+                }
+                if policy.keep_last.is_some_and(|keep_last| index < keep_last as usize) {
+                    reasons.push("recent".to_string());
+                }
+                if policy.keep_daily && days_seen.insert(created_day(doc)) {
+                    reasons.push("daily".to_string());
+                }
+            }
+        }
+
+        if reasons.is_empty() {
+            removed.push(*number);
+            bytes_freed += doc
+                .data
+                .get("source_code")
+                .and_then(|v| v.as_str())
+                .map(|s| s.len())
+                .unwrap_or(0);
+        } else {
+            kept.push(RetainedVersion {
+                version_number: *number,
+                reasons,
+            });
+        }
+    }
+
+    kept.reverse();
+    removed.reverse();
+    PruneReport {
+        project_id: project_id.to_string(),
+        dry_run: true,
+        kept,
+        removed,
+        bytes_freed,
+    }
+}
+
+fn version_number(doc: &Document) -> i32 {
+    doc.data.get("version_number").and_then(|v| v.as_i64()).unwrap_or(0) as i32
+}
+
+fn created_day(doc: &Document) -> String {
+    doc.data
+        .get("created_at")
+        .and_then(|v| v.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+        .unwrap_or(doc.created_at)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// Versions a version points at, with the reason each must be kept for it
+fn version_references(doc: &Document) -> Vec<(i32, &'static str)> {
+    let data = &doc.data;
+    [
+        (data.get("parent_version"), "parent"),
+        (data.get("reverted_from"), "reverted_from"),
+        (data.get("merged_from"), "merged_from"),
+        (data.get("changelog").and_then(|c| c.get("from_version")), "changelog_base"),
+    ]
+    .into_iter()
+    .filter_map(|(version, reason)| version.and_then(|v| v.as_i64()).map(|v| (v as i32, reason)))
+    .collect()
+}
+
+impl Database {
+    /// Apply a project's retention policy, deleting the versions it doesn't keep unless
+    /// `dry_run` is set. Returns `None` if the project doesn't exist.
+    ///
+    /// Besides what the policy keeps, versions that something still points at survive: branch
+    /// heads, the app's current and previous release, forks made from the project, and the
+    /// versions a kept version was built on, reverted or merged from, or has a changelog
+    /// against. Planning and deleting happen in one transaction.
+    pub async fn prune_project_versions(
+        &self,
+        project_id: &str,
+        dry_run: bool,
+    ) -> Result<Option<PruneReport>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Writing first takes SQLite's write lock, so no version, release or fork can appear
+        // between planning and deleting
+        sqlx::query(
+            r#"
+            UPDATE documents
+            SET updated_at = updated_at
+            WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+            "#,
+        )
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let ids = [project_id.to_string()];
+        let project_doc = match documents_where_in(&mut tx, "projects", "id", &ids).await?.pop() {
+            Some(doc) => doc,
+            None => return Ok(None),
+        };
+        let policy: Option<RetentionPolicy> = project_doc
+            .data
+            .get("retention")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+        let versions = documents_where_in(&mut tx, "project_versions", "project_id", &ids).await?;
+        let released: HashSet<i32> = documents_where_in(&mut tx, "releases", "project_id", &ids)
+            .await?
+            .iter()
+            .filter_map(|doc| doc.data.get("project_version").and_then(|v| v.as_i64()))
+            .map(|n| n as i32)
+            .collect();
+
+        let mut protected: BTreeMap<i32, Vec<&'static str>> = BTreeMap::new();
+        for head in project_branches(&project_doc.data).into_values() {
+            protected.entry(head).or_default().push("branch_head");
+        }
+        // The app must be able to keep running its release and to downgrade
+        for app_doc in documents_where_in(&mut tx, "apps", "project_id", &ids).await? {
+            let current = app_doc.data.get("project_version").and_then(|v| v.as_i64());
+            let previous = app_doc
+                .data
+                .get("previous_release")
+                .and_then(|v| v.get("project_version"))
+                .and_then(|v| v.as_i64());
+            for (version, reason) in [(current, "app_current"), (previous, "app_previous")] {
+                if let Some(version) = version {
+                    protected.entry(version as i32).or_default().push(reason);
+                }
+            }
+        }
+        for fork_doc in documents_where_in(&mut tx, "projects", "forked_from.project_id", &ids).await? {
+            let forked = fork_doc
+                .data
+                .get("forked_from")
+                .and_then(|v| v.get("version_number"))
+                .and_then(|v| v.as_i64());
+            if let Some(version) = forked {
+                protected.entry(version as i32).or_default().push("forked");
+            }
+        }
+
+        // A kept version keeps what it points at, which may in turn point further back
+        let existing: HashSet<i32> = versions.iter().map(version_number).collect();
+        let mut referenced: HashSet<(i32, &'static str)> = HashSet::new();
+        let mut report = loop {
+            let report = plan_retention(project_id, policy.as_ref(), &versions, &released, &protected);
+            let kept: HashSet<i32> = report.kept.iter().map(|k| k.version_number).collect();
+            let mut grew = false;
+            for doc in versions.iter().filter(|doc| kept.contains(&version_number(doc))) {
+                for reference in version_references(doc) {
+                    if existing.contains(&reference.0) && referenced.insert(reference) {
+                        protected.entry(reference.0).or_default().push(reference.1);
+                        grew = true;
+                    }
+                }
+            }
+            if !grew {
+                break report;
+            }
+        };
+        report.dry_run = dry_run;
+
+        if dry_run || report.removed.is_empty() {
+            return Ok(Some(report));
+        }
+
+        let removed: HashSet<i32> = report.removed.iter().copied().collect();
+        for doc in versions.iter().filter(|doc| removed.contains(&version_number(doc))) {
+            sqlx::query("DELETE FROM documents WHERE collection = 'project_versions' AND id = ?")
+                .bind(&doc.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        self.collect_unreferenced_sources().await?;
+
+        Ok(Some(report))
+    }
+
+    /// Prune every project that has a retention policy, returning how many versions went
+    pub async fn prune_all_projects(&self) -> Result<usize, sqlx::Error> {
+        const PAGE_SIZE: i64 = 1000;
+
+        let mut removed = 0;
+        let mut offset = 0;
+        loop {
+            let projects = self.list_documents("projects", Some(PAGE_SIZE), Some(offset)).await?;
+            let page_len = projects.documents.len() as i64;
+            for project_doc in projects.documents {
+                if project_doc.data.get("retention").is_none() {
+                    continue;
+                }
+                let Some(project_id) = project_doc.data.get("id").and_then(|v| v.as_str()) else {
+                    continue;
+                };
+                if let Some(report) = self.prune_project_versions(project_id, false).await? {
+                    removed += report.removed.len();
+                }
+            }
+            if page_len < PAGE_SIZE {
+                break;
+            }
+            offset += PAGE_SIZE;
+        }
+        Ok(removed)
+    }
+}
+
+/// Prune all projects every `VERSION_PRUNE_INTERVAL_SECS` in the background
+pub fn spawn_pruning_job(database: Arc<Database>) {
+    let interval_secs = Config::version_prune_interval_secs();
+    if interval_secs == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        // The first tick fires immediately; let the server finish starting up first
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match database.prune_all_projects().await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("Pruned {} old project versions", removed),
+                Err(e) => tracing::error!("Version pruning failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+
+use crate::capabilities::{connect_sources, effective_permissions};
+use crate::config::Config;
+use crate::models::App;
+
+/// HTML shell every standalone app page is rendered from
+const PAGE_TEMPLATE: &str = include_str!("../runtime/app-page.html");
+
+/// Theme variables and `ui` kit styles for standalone app pages
+pub const RUNTIME_STYLES: &str = include_str!("../runtime/app-runtime.css");
+
+/// The `ui` kit, toast and hostAPI shims plus the bootstrap that renders the app
+pub const RUNTIME_SCRIPT: &str = include_str!("../runtime/app-runtime.js");
+
+/// Background and theme color of app pages, matching the dashboard's light theme
+pub const APP_THEME_COLOR: &str = "#ffffff";
+
+/// Render the page that runs `app` outside the dashboard, talking to the API at `/api` with
+/// the app token the dashboard passes in the URL fragment when it launches the app
+pub fn render_run_page(app: &App) -> String {
+    let head = format!(
+        concat!(
+            "    <link rel=\"manifest\" href=\"manifest.webmanifest\" />\n",
+            "    <link rel=\"icon\" href=\"icon.svg\" type=\"image/svg+xml\" />\n",
+            "    <meta name=\"theme-color\" content=\"{}\" />\n",
+            "    <meta name=\"description\" content=\"{}\" />",
+        ),
+        APP_THEME_COLOR,
+        escape_html(&app.description),
+    );
+    let scripts = [Config::react_url(), Config::react_dom_url()]
+        .iter()
+        .map(|url| format!("    <script src=\"{}\" crossorigin></script>", escape_html(url)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    render_page(app, &head, &scripts, Some("/api"))
+}
+
+/// Content-Security-Policy for an app's run page: it runs sandboxed in an opaque origin, so
+/// it can't reach this server's storage or other pages, and may only connect to this server
+/// and the hosts it was granted
+pub fn run_page_policy(app: &App) -> String {
+    let mut sources = vec!["'self'".to_string()];
+    sources.extend(connect_sources(&effective_permissions(app)));
+    format!(
+        "connect-src {}; sandbox allow-scripts allow-forms allow-modals allow-popups allow-downloads",
+        sources.join(" ")
+    )
+}
+
+/// Render a single HTML file that runs `app` with no server: React, the runtime and the app
+/// source are inlined, and the app's data is kept in the browser
+pub fn render_export_page(app: &App, react_bundle: &str) -> String {
+    // Exported files travel without a grant, so only the hosts the app declares are allowed
+    let hosts = connect_sources(app.manifest.as_ref().unwrap_or(&Default::default()));
+    let connect_src = if hosts.is_empty() {
+        "'none'".to_string()
+    } else {
+        hosts.join(" ")
+    };
+    let icon = format!(
+        "data:image/svg+xml,{}",
+        app_icon_svg(app).replace('%', "%25").replace('#', "%23")
+    );
+    let head = format!(
+        concat!(
+            "    <meta http-equiv=\"Content-Security-Policy\" content=\"connect-src {}\" />\n",
+            "    <link rel=\"icon\" href=\"{}\" />\n",
+            "    <meta name=\"theme-color\" content=\"{}\" />\n",
+            "    <meta name=\"description\" content=\"{}\" />",
+        ),
+        escape_html(&connect_src),
+        escape_html(&icon),
+        APP_THEME_COLOR,
+        escape_html(&app.description),
+    );
+    let scripts = format!("    <script>\n{}\n    </script>", inline_script(react_bundle));
+
+    render_page(app, &head, &scripts, None)
+}
+
+/// React and ReactDOM from `REACT_URL` and `REACT_DOM_URL`, for inlining into exported apps.
+///
+/// The server loads them at startup (see `preload_react_bundle`), so exports don't wait on
+/// the download. If that failed they are downloaded again on the next export; concurrent
+/// exports share one download, which gives up after `REACT_DOWNLOAD_TIMEOUT_SECS`.
+pub async fn react_bundle() -> Result<&'static str, reqwest::Error> {
+    static BUNDLE: OnceCell<String> = OnceCell::const_new();
+    let bundle = BUNDLE
+        .get_or_try_init(|| async {
+            let client = Client::builder()
+                .timeout(Duration::from_secs(Config::react_download_timeout_secs()))
+                .build()?;
+            let mut bundle = String::new();
+            for url in [Config::react_url(), Config::react_dom_url()] {
+                let script = client.get(&url).send().await?.error_for_status()?.text().await?;
+                bundle.push_str(&script);
+                bundle.push('\n');
+            }
+            Ok(bundle)
+        })
+        .await?;
+    Ok(bundle)
+}
+
+/// Start loading the React bundle in the background, so the first export finds it ready
+pub fn preload_react_bundle() {
+    tokio::spawn(async {
+        if let Err(e) = react_bundle().await {
+            tracing::warn!("Failed to load React for exported apps, will retry on export: {}", e);
+        }
+    });
+}
+
+/// Fill the page template in for an app.
+///
+/// `head` and `scripts` are inserted as they are. `api_base` is where the runtime calls the
+/// host API; without it the runtime keeps the app's data in the browser instead of on the
+/// server.
+pub fn render_page(app: &App, head: &str, scripts: &str, api_base: Option<&str>) -> String {
+    let app_data = json!({
+        "app": app,
+        "apiBase": api_base,
+    });
+    fill_template(
+        PAGE_TEMPLATE,
+        &[
+            ("title", escape_html(&app.name)),
+            ("head", head.to_string()),
+            ("styles", RUNTIME_STYLES.to_string()),
+            ("scripts", scripts.to_string()),
+            ("app_data", script_json(&app_data)),
+            ("runtime", inline_script(RUNTIME_SCRIPT)),
+        ],
+    )
+}
+
+/// Web app manifest letting browsers install an app as its own PWA
+pub fn app_manifest(app: &App) -> Value {
+    json!({
+        "id": format!("/apps/{}/run", app.id),
+        "name": app.name,
+        "short_name": app.name,
+        "description": app.description,
+        "start_url": "run",
+        "scope": "./",
+        "display": "standalone",
+        "background_color": APP_THEME_COLOR,
+        "theme_color": APP_THEME_COLOR,
+        "icons": [
+            {
+                "src": "icon.svg",
+                "type": "image/svg+xml",
+                "sizes": "any",
+                "purpose": "any"
+            }
+        ]
+    })
+}
+
+/// SVG icon drawing an app's emoji icon
+pub fn app_icon_svg(app: &App) -> String {
+    format!(
+        concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\">",
+            "<rect width=\"100\" height=\"100\" rx=\"20\" fill=\"{}\"/>",
+            "<text x=\"50\" y=\"50\" font-size=\"64\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+            "</svg>",
+        ),
+        APP_THEME_COLOR,
+        escape_html(&app.icon),
+    )
+}
+
+/// Replace each `{{name}}` in `template` in a single pass, so values containing placeholders
+/// (app source code, say) are left alone
+fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut page = String::with_capacity(template.len() + values.iter().map(|(_, v)| v.len()).sum::<usize>());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        let name = &rest[start + 2..end];
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => {
+                page.push_str(&rest[..start]);
+                page.push_str(value);
+            }
+            None => page.push_str(&rest[..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    page.push_str(rest);
+    page
+}
+
+/// Script source that can't close the `<script>` element it is inlined into.
+///
+/// Every `</` is escaped, whatever its case or whatever follows it; in JavaScript strings,
+/// regular expressions and comments `<\/` means the same thing.
+fn inline_script(source: &str) -> String {
+    source.replace("</", "<\\/")
+}
+
+/// JSON that is safe to put inside a `<script>` element
+fn script_json(value: &Value) -> String {
+    // `<` only ever appears inside strings, where the escape means the same thing
+    serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('<', "\\u003c")
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
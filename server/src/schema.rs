@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::DateTime;
+use serde_json::{json, Map, Value};
+
+/// Maximum number of distinct example values kept per field
+const MAX_EXAMPLES: usize = 3;
+
+/// Example strings longer than this are truncated (source code, long notes, ...)
+const MAX_EXAMPLE_LENGTH: usize = 120;
+
+/// Infer a JSON Schema describing the `data` of the given documents
+pub fn infer_schema(collection: &str, samples: &[Value]) -> Value {
+    let mut stats = ValueStats::default();
+    for sample in samples {
+        stats.observe(sample);
+    }
+
+    let mut schema = stats.to_schema();
+    if let Some(schema_obj) = schema.as_object_mut() {
+        schema_obj.insert(
+            "$schema".to_string(),
+            json!("https://json-schema.org/draft/2020-12/schema"),
+        );
+        schema_obj.insert("title".to_string(), json!(collection));
+    }
+    schema
+}
+
+/// Statistics collected for every value seen at one position of the document tree
+#[derive(Default)]
+struct ValueStats {
+    seen: usize,
+    types: BTreeSet<&'static str>,
+    examples: Vec<Value>,
+    all_date_time: bool,
+    strings: usize,
+    objects: usize,
+    properties: BTreeMap<String, ValueStats>,
+    items: Option<Box<ValueStats>>,
+}
+
+impl ValueStats {
+    fn observe(&mut self, value: &Value) {
+        self.seen += 1;
+        self.types.insert(json_type(value));
+
+        match value {
+            Value::Object(map) => {
+                self.objects += 1;
+                for (key, field) in map {
+                    self.properties.entry(key.clone()).or_default().observe(field);
+                }
+            }
+            Value::Array(items) => {
+                let item_stats = self.items.get_or_insert_with(Default::default);
+                for item in items {
+                    item_stats.observe(item);
+                }
+            }
+            Value::Null => {}
+            Value::String(s) => {
+                let is_date_time = DateTime::parse_from_rfc3339(s).is_ok();
+                self.all_date_time = is_date_time && (self.strings == 0 || self.all_date_time);
+                self.strings += 1;
+                self.add_example(Value::String(truncate_example(s)));
+            }
+            _ => self.add_example(value.clone()),
+        }
+    }
+
+    fn add_example(&mut self, example: Value) {
+        if self.examples.len() < MAX_EXAMPLES && !self.examples.contains(&example) {
+            self.examples.push(example);
+        }
+    }
+
+    fn to_schema(&self) -> Value {
+        let mut schema = Map::new();
+
+        // An integer field that sometimes holds floats is just a number
+        let mut types: Vec<&str> = self.types.iter().copied().collect();
+        if types.contains(&"number") {
+            types.retain(|t| *t != "integer");
+        }
+        match types.as_slice() {
+            [] => {}
+            [single] => {
+                schema.insert("type".to_string(), json!(single));
+            }
+            _ => {
+                schema.insert("type".to_string(), json!(types));
+            }
+        }
+
+        if self.strings > 0 && self.all_date_time {
+            schema.insert("format".to_string(), json!("date-time"));
+        }
+
+        if self.objects > 0 {
+            let properties: Map<String, Value> = self
+                .properties
+                .iter()
+                .map(|(key, stats)| (key.clone(), stats.to_schema()))
+                .collect();
+            let required: Vec<&String> = self
+                .properties
+                .iter()
+                .filter(|(_, stats)| stats.seen == self.objects)
+                .map(|(key, _)| key)
+                .collect();
+
+            schema.insert("properties".to_string(), Value::Object(properties));
+            schema.insert("required".to_string(), json!(required));
+        }
+
+        if let Some(items) = &self.items {
+            schema.insert("items".to_string(), items.to_schema());
+        }
+
+        if !self.examples.is_empty() {
+            schema.insert("examples".to_string(), json!(self.examples));
+        }
+
+        Value::Object(schema)
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn truncate_example(s: &str) -> String {
+    if s.chars().count() <= MAX_EXAMPLE_LENGTH {
+        s.to_string()
+    } else {
+        let truncated: String = s.chars().take(MAX_EXAMPLE_LENGTH).collect();
+        format!("{}…", truncated)
+    }
+}
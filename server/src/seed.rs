@@ -1,6 +1,8 @@
 use serde_json::json;
 
+use crate::capabilities::{infer_manifest, ANY_COLLECTION};
 use crate::database::Database;
+use crate::templates::builtin_template;
 
 impl Database {
     pub async fn seed_default_apps(&self) -> Result<(), sqlx::Error> {
@@ -10,26 +12,42 @@ impl Database {
         if existing_apps.documents.is_empty() {
             tracing::info!("No apps found, seeding default apps");
 
+            // Both ship in the templates directory
+            let notepad = builtin_template("notepad").expect("notepad template is built in");
+            let db_viewer = builtin_template("db-viewer").expect("db-viewer template is built in");
+
+            // Apps can't do anything until granted it, and the defaults come granted what
+            // they use
+            let notepad_manifest = infer_manifest(notepad.source_code);
+            let db_viewer_manifest = json!({
+                "collections": [{ "name": ANY_COLLECTION, "access": "read" }]
+            });
+
             let default_apps = vec![
                 json!({
-                    "id": "notepad",
-                    "name": "Notepad",
-                    "description": "A simple notepad for quick notes and ideas.",
+                    "id": notepad.id,
+                    "name": notepad.name,
+                    "description": notepad.description,
                     "version": "1.0.0",
                     "price": 0,
-                    "icon": "📝",
-                    "installed": 1,
-                    "source_code": include_str!("../templates/notepad.js")
+                    "icon": notepad.icon,
+                    "installed": true,
+                    "source_code": notepad.source_code,
+                    "manifest": notepad_manifest,
+                    "granted": notepad_manifest
                 }),
                 json!({
-                    "id": "db-viewer",
-                    "name": "DB Viewer",
-                    "description": "Browse and manage your database collections and documents.",
+                    "id": db_viewer.id,
+                    "name": db_viewer.name,
+                    "description": db_viewer.description,
                     "version": "1.0.0",
                     "price": 0,
-                    "icon": "🗃️",
-                    "installed": 1,
-                    "source_code": include_str!("../templates/db-viewer.js")
+                    "icon": db_viewer.icon,
+                    "installed": true,
+                    "source_code": db_viewer.source_code,
+                    // Browses every collection by name, which can't be worked out from its source
+                    "manifest": db_viewer_manifest,
+                    "granted": db_viewer_manifest
                 }),
                 json!({
                     "id": "to-do-list",
@@ -38,7 +56,7 @@ impl Database {
                     "version": "1.2.3",
                     "price": 2.99,
                     "icon": "✅",
-                    "installed": 0
+                    "installed": false
                 }),
                 json!({
                     "id": "calendar",
@@ -47,7 +65,7 @@ impl Database {
                     "version": "2.1.0",
                     "price": 4.99,
                     "icon": "📅",
-                    "installed": 0
+                    "installed": false
                 }),
                 json!({
                     "id": "chess",
@@ -56,7 +74,7 @@ impl Database {
                     "version": "1.8.7",
                     "price": 7.50,
                     "icon": "♟️",
-                    "installed": 0
+                    "installed": false
                 }),
                 json!({
                     "id": "file-drive",
@@ -65,7 +83,7 @@ impl Database {
                     "version": "3.0.2",
                     "price": 9.99,
                     "icon": "🗂️",
-                    "installed": 0
+                    "installed": false
                 }),
                 json!({
                     "id": "calculator",
@@ -74,7 +92,7 @@ impl Database {
                     "version": "2.4.1",
                     "price": 1.99,
                     "icon": "🧮",
-                    "installed": 0
+                    "installed": false
                 }),
                 json!({
                     "id": "stocks",
@@ -83,7 +101,7 @@ impl Database {
                     "version": "1.5.9",
                     "price": 8.99,
                     "icon": "📈",
-                    "installed": 0
+                    "installed": false
                 }),
             ];
 
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{QueryBuilder, Row, Sqlite, Transaction};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{ResetToken, Snapshot};
+
+/// How long a prepared reset stays valid
+const RESET_TOKEN_TTL_MINUTES: i64 = 5;
+
+impl Database {
+    pub(crate) async fn create_snapshot_tables(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS snapshots (
+                id TEXT PRIMARY KEY,
+                reason TEXT NOT NULL,
+                collections TEXT,
+                document_count INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS snapshot_documents (
+                snapshot_id TEXT NOT NULL,
+                id TEXT NOT NULL,
+                collection TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (snapshot_id, id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS reset_tokens (
+                token TEXT PRIMARY KEY,
+                collections TEXT,
+                expires_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Snapshot the given collections (or every document) and clear them in the same
+    /// transaction, so data is never removed without a way back
+    pub async fn reset_with_snapshot(
+        &self,
+        reason: &str,
+        collections: Option<&[String]>,
+    ) -> Result<Snapshot, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let snapshot = insert_snapshot(&mut tx, reason, collections).await?;
+
+        let mut clear = QueryBuilder::<Sqlite>::new("DELETE FROM documents");
+        push_collection_filter(&mut clear, collections);
+        clear.build().execute(&mut *tx).await?;
+
+        prune_snapshots(&mut tx, Config::snapshot_retention()).await?;
+
+        tx.commit().await?;
+
+        // Re-seed default apps when they were cleared
+        if collections.is_none_or(|c| c.iter().any(|c| c == "apps")) {
+            self.seed_default_apps().await?;
+        }
+
+        Ok(snapshot)
+    }
+
+    pub async fn list_snapshots(&self) -> Result<Vec<Snapshot>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, reason, collections, document_count, created_at
+            FROM snapshots
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(snapshot_from_row).collect())
+    }
+
+    pub async fn get_snapshot(&self, id: &str) -> Result<Option<Snapshot>, sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, reason, collections, document_count, created_at
+            FROM snapshots
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(snapshot_from_row))
+    }
+
+    /// Replace the snapshotted collections (or the whole database) with the snapshot contents
+    pub async fn restore_snapshot(&self, id: &str) -> Result<Option<Snapshot>, sqlx::Error> {
+        let snapshot = match self.get_snapshot(id).await? {
+            Some(snapshot) => snapshot,
+            None => return Ok(None),
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        let mut clear = QueryBuilder::<Sqlite>::new("DELETE FROM documents");
+        push_collection_filter(&mut clear, snapshot.collections.as_deref());
+        clear.build().execute(&mut *tx).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO documents (id, collection, data, created_at, updated_at)
+            SELECT id, collection, data, created_at, updated_at
+            FROM snapshot_documents
+            WHERE snapshot_id = ?
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        // Snapshots taken before sources were content-addressed still hold them inline
+        self.migrate_inline_sources().await?;
+
+        Ok(Some(snapshot))
+    }
+
+    /// Issue a single-use token that authorizes one reset of the given scope
+    pub async fn prepare_reset(
+        &self,
+        collections: Option<Vec<String>>,
+    ) -> Result<ResetToken, sqlx::Error> {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + Duration::minutes(RESET_TOKEN_TTL_MINUTES);
+        let collections_json = collections.as_ref().map(|c| serde_json::to_string(c).unwrap());
+
+        // Drop tokens nobody used
+        sqlx::query("DELETE FROM reset_tokens WHERE expires_at < ?")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("INSERT INTO reset_tokens (token, collections, expires_at) VALUES (?, ?, ?)")
+            .bind(&token)
+            .bind(&collections_json)
+            .bind(expires_at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) AS count FROM documents");
+        push_collection_filter(&mut count, collections.as_deref());
+        let document_count: i64 = count.build().fetch_one(&self.pool).await?.get("count");
+
+        Ok(ResetToken {
+            token,
+            collections,
+            document_count,
+            expires_at,
+        })
+    }
+
+    /// Consume a reset token, returning its scope if it was valid and not expired
+    pub async fn consume_reset_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<Option<Vec<String>>>, sqlx::Error> {
+        let row = sqlx::query("DELETE FROM reset_tokens WHERE token = ? RETURNING collections, expires_at")
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let expires_at = DateTime::parse_from_rfc3339(row.get("expires_at"))
+            .unwrap()
+            .with_timezone(&Utc);
+        if expires_at < Utc::now() {
+            return Ok(None);
+        }
+
+        let collections: Option<String> = row.get("collections");
+        Ok(Some(collections.map(|c| serde_json::from_str(&c).unwrap())))
+    }
+}
+
+/// Copy the given collections (or every document) into a new snapshot
+async fn insert_snapshot(
+    tx: &mut Transaction<'_, Sqlite>,
+    reason: &str,
+    collections: Option<&[String]>,
+) -> Result<Snapshot, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now();
+    let collections_json = collections.map(|c| serde_json::to_string(c).unwrap());
+
+    let mut copy = QueryBuilder::<Sqlite>::new(
+        "INSERT INTO snapshot_documents (snapshot_id, id, collection, data, created_at, updated_at) SELECT ",
+    );
+    copy.push_bind(&id);
+    copy.push(", id, collection, data, created_at, updated_at FROM documents");
+    push_collection_filter(&mut copy, collections);
+    let document_count = copy.build().execute(&mut **tx).await?.rows_affected() as i64;
+
+    sqlx::query(
+        r#"
+        INSERT INTO snapshots (id, reason, collections, document_count, created_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&id)
+    .bind(reason)
+    .bind(&collections_json)
+    .bind(document_count)
+    .bind(now.to_rfc3339())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(Snapshot {
+        id,
+        reason: reason.to_string(),
+        collections: collections.map(|c| c.to_vec()),
+        document_count,
+        created_at: now,
+    })
+}
+
+/// Delete all but the `keep` newest snapshots; 0 keeps every snapshot
+async fn prune_snapshots(tx: &mut Transaction<'_, Sqlite>, keep: u32) -> Result<(), sqlx::Error> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    let expired = "SELECT id FROM snapshots ORDER BY created_at DESC LIMIT -1 OFFSET ?";
+    sqlx::query(&format!("DELETE FROM snapshot_documents WHERE snapshot_id IN ({})", expired))
+        .bind(keep)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(&format!("DELETE FROM snapshots WHERE id IN ({})", expired))
+        .bind(keep)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+fn push_collection_filter(query: &mut QueryBuilder<'_, Sqlite>, collections: Option<&[String]>) {
+    if let Some(collections) = collections {
+        if collections.is_empty() {
+            query.push(" WHERE 0");
+            return;
+        }
+        query.push(" WHERE collection IN (");
+        let mut separated = query.separated(", ");
+        for collection in collections {
+            separated.push_bind(collection.clone());
+        }
+        separated.push_unseparated(")");
+    }
+}
+
+fn snapshot_from_row(row: &sqlx::sqlite::SqliteRow) -> Snapshot {
+    let collections: Option<String> = row.get("collections");
+    Snapshot {
+        id: row.get("id"),
+        reason: row.get("reason"),
+        collections: collections.map(|c| serde_json::from_str(&c).unwrap()),
+        document_count: row.get("document_count"),
+        created_at: DateTime::parse_from_rfc3339(row.get("created_at"))
+            .unwrap()
+            .with_timezone(&Utc),
+    }
+}
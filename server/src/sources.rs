@@ -0,0 +1,205 @@
+use chrono::Utc;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection};
+
+use crate::database::Database;
+use crate::models::Document;
+
+/// Collections whose `source_code` lives in the content-addressed `sources` table.
+///
+/// Their stored data holds only a `source_hash`; the source is put back into `data` whenever
+/// the document is read.
+pub const SOURCE_COLLECTIONS: &[&str] = &["project_versions", "apps"];
+
+/// Hex-encoded SHA-256 of `bytes`
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Whether `hash` looks like a hex SHA-256 digest
+pub fn is_source_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Split a document's source code out of its data.
+///
+/// Returns the data to store, without `source_code`, and the hash and source to save in the
+/// `sources` table. `data` keeps its source and gains the `source_hash`. Documents outside
+/// [`SOURCE_COLLECTIONS`] or without source are stored as they are.
+pub(crate) fn split_source(collection: &str, data: &mut Value) -> (Value, Option<(String, String)>) {
+    if !SOURCE_COLLECTIONS.contains(&collection) {
+        return (data.clone(), None);
+    }
+    let source_code = match data.get("source_code").and_then(|v| v.as_str()) {
+        Some(source_code) => source_code.to_string(),
+        None => return (data.clone(), None),
+    };
+
+    let hash = sha256_hex(source_code.as_bytes());
+    if let Some(data_obj) = data.as_object_mut() {
+        data_obj.insert("source_hash".to_string(), Value::String(hash.clone()));
+    }
+    let mut stored = data.clone();
+    if let Some(stored_obj) = stored.as_object_mut() {
+        stored_obj.remove("source_code");
+    }
+    (stored, Some((hash, source_code)))
+}
+
+/// Save a source under its hash; sources already stored are left alone
+pub(crate) async fn save_source(
+    conn: &mut SqliteConnection,
+    hash: &str,
+    source_code: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO sources (hash, content, size, created_at)
+        VALUES (?, ?, ?, ?)
+        "#,
+    )
+    .bind(hash)
+    .bind(source_code)
+    .bind(source_code.len() as i64)
+    .bind(Utc::now().to_rfc3339())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+impl Database {
+    pub(crate) async fn create_sources_table(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS sources (
+                hash TEXT PRIMARY KEY,
+                content TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Move source code still stored inline in documents into the `sources` table
+    pub(crate) async fn migrate_inline_sources(&self) -> Result<usize, sqlx::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT id, collection, data FROM documents WHERE collection IN (");
+        let mut separated = query.separated(", ");
+        for collection in SOURCE_COLLECTIONS {
+            separated.push_bind(*collection);
+        }
+        separated.push_unseparated(") AND json_extract(data, '$.source_code') IS NOT NULL");
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        let mut tx = self.pool.begin().await?;
+        for row in &rows {
+            let collection: String = row.get("collection");
+            let mut data: Value = serde_json::from_str(row.get("data")).unwrap();
+            let (stored, source) = split_source(&collection, &mut data);
+            if let Some((hash, source_code)) = source {
+                save_source(&mut tx, &hash, &source_code).await?;
+            }
+            sqlx::query("UPDATE documents SET data = ? WHERE id = ?")
+                .bind(serde_json::to_string(&stored).unwrap())
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        if !rows.is_empty() {
+            tracing::info!("Moved {} inline sources into the sources table", rows.len());
+        }
+        Ok(rows.len())
+    }
+
+    /// Put each document's source code back into its data, looked up by `source_hash`.
+    /// Documents outside `SOURCE_COLLECTIONS` are left as they are.
+    pub(crate) async fn hydrate_sources(&self, documents: &mut [Document]) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        hydrate_sources_in(&mut conn, documents).await
+    }
+
+    /// Look a stored source up by its hash
+    pub async fn get_source(&self, hash: &str) -> Result<Option<String>, sqlx::Error> {
+        let row = sqlx::query("SELECT content FROM sources WHERE hash = ?")
+            .bind(hash)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get("content")))
+    }
+
+    /// Delete sources no document or snapshot refers to any more, returning how many went
+    pub async fn collect_unreferenced_sources(&self) -> Result<u64, sqlx::Error> {
+        let deleted = sqlx::query(
+            r#"
+            DELETE FROM sources
+            WHERE hash NOT IN (
+                SELECT json_extract(data, '$.source_hash') FROM documents
+                WHERE json_extract(data, '$.source_hash') IS NOT NULL
+            )
+            AND hash NOT IN (
+                SELECT json_extract(data, '$.source_hash') FROM snapshot_documents
+                WHERE json_extract(data, '$.source_hash') IS NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(deleted)
+    }
+}
+
+/// Put each document's source code back into its data, reading through `conn`, e.g. an open
+/// transaction
+pub(crate) async fn hydrate_sources_in(
+    conn: &mut SqliteConnection,
+    documents: &mut [Document],
+) -> Result<(), sqlx::Error> {
+    let mut hashes: Vec<String> = documents
+        .iter()
+        .filter(|doc| SOURCE_COLLECTIONS.contains(&doc.collection.as_str()))
+        .filter_map(|doc| doc.data.get("source_hash").and_then(|v| v.as_str()))
+        .map(|hash| hash.to_string())
+        .collect();
+    hashes.sort();
+    hashes.dedup();
+    if hashes.is_empty() {
+        return Ok(());
+    }
+
+    let mut query = QueryBuilder::<Sqlite>::new("SELECT hash, content FROM sources WHERE hash IN (");
+    let mut separated = query.separated(", ");
+    for hash in &hashes {
+        separated.push_bind(hash.clone());
+    }
+    separated.push_unseparated(")");
+    let sources: std::collections::HashMap<String, String> = query
+        .build()
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.get("hash"), row.get("content")))
+        .collect();
+
+    for doc in documents.iter_mut() {
+        if !SOURCE_COLLECTIONS.contains(&doc.collection.as_str()) {
+            continue;
+        }
+        let source = doc
+            .data
+            .get("source_hash")
+            .and_then(|v| v.as_str())
+            .and_then(|hash| sources.get(hash));
+        if let (Some(source), Some(data_obj)) = (source.cloned(), doc.data.as_object_mut()) {
+            data_obj.insert("source_code".to_string(), Value::String(source));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+/// A template shipped with the server, embedded at compile time from `templates/<id>.js`
+pub struct BuiltinTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub icon: &'static str,
+    pub source_code: &'static str,
+}
+
+/// Every template in the `templates` directory, ordered by id (generated by `build.rs`)
+pub const BUILTIN_TEMPLATES: &[BuiltinTemplate] =
+    include!(concat!(env!("OUT_DIR"), "/builtin_templates.rs"));
+
+pub fn builtin_template(id: &str) -> Option<&'static BuiltinTemplate> {
+    BUILTIN_TEMPLATES.iter().find(|template| template.id == id)
+}
@@ -0,0 +1,403 @@
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{Argument, CallExpression, Expression, NewExpression, Program, Statement, StaticMemberExpression};
+use oxc_ast_visit::{walk, Visit};
+use oxc_parser::{ParseOptions, Parser};
+use oxc_span::SourceType;
+
+use crate::models::{DiagnosticSeverity, SourceDiagnostic, SourceValidation};
+
+/// Syntax errors reported before the rest are left out, since later ones usually follow from
+/// the first
+const MAX_SYNTAX_ERRORS: usize = 5;
+
+impl SourceValidation {
+    pub fn has_errors(&self) -> bool {
+        !self.valid
+    }
+}
+
+/// Check app source the way it will run: as the body of a function that returns the app's
+/// React component, e.g. `function MyApp(props) { ... } return MyApp;`.
+///
+/// Finds syntax errors (naming TypeScript, JSX and markdown fences, which generated code
+/// sometimes contains), a missing component, and constructs apps may not use.
+pub fn validate_source(source_code: &str) -> SourceValidation {
+    let mut diagnostics = Vec::new();
+
+    // A fenced answer won't parse; say why rather than listing the parse errors it causes
+    let fences: Vec<usize> = source_code
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with("```"))
+        .map(|(index, _)| index + 1)
+        .collect();
+    if !fences.is_empty() {
+        for line in fences {
+            diagnostics.push(SourceDiagnostic {
+                severity: DiagnosticSeverity::Error,
+                code: "markdown_fence".to_string(),
+                message: "Markdown code fences aren't JavaScript; remove them".to_string(),
+                line: Some(line),
+                column: Some(1),
+            });
+        }
+        return finish(diagnostics);
+    }
+
+    let allocator = Allocator::default();
+    let parsed = parse(&allocator, source_code, SourceType::cjs());
+    if !parsed.errors.is_empty() {
+        diagnostics.extend(syntax_diagnostics(source_code, &parsed.errors));
+        return finish(diagnostics);
+    }
+
+    diagnostics.extend(component_diagnostics(source_code, &parsed.program));
+
+    let mut checker = ConstructChecker {
+        source_code,
+        diagnostics: Vec::new(),
+    };
+    checker.visit_program(&parsed.program);
+    diagnostics.extend(checker.diagnostics);
+
+    finish(diagnostics)
+}
+
+fn parse<'a>(allocator: &'a Allocator, source_code: &'a str, source_type: SourceType) -> oxc_parser::ParserReturn<'a> {
+    Parser::new(allocator, source_code, source_type)
+        .with_options(ParseOptions {
+            allow_return_outside_function: true,
+            ..ParseOptions::default()
+        })
+        .parse()
+}
+
+fn finish(diagnostics: Vec<SourceDiagnostic>) -> SourceValidation {
+    SourceValidation {
+        valid: !diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error),
+        diagnostics,
+    }
+}
+
+/// Report parse errors, or a single error naming the language the source is really in when
+/// it parses as TypeScript or JSX
+fn syntax_diagnostics(source_code: &str, errors: &[oxc_diagnostics::OxcDiagnostic]) -> Vec<SourceDiagnostic> {
+    let first_offset = errors.first().and_then(error_offset);
+
+    let dialects = [
+        (
+            SourceType::ts(),
+            "typescript",
+            "TypeScript syntax such as type annotations can't run; use plain JavaScript",
+        ),
+        (
+            SourceType::jsx(),
+            "jsx",
+            "JSX can't run; build elements with React.createElement",
+        ),
+    ];
+    for (source_type, code, message) in dialects {
+        let allocator = Allocator::default();
+        if parse(&allocator, source_code, source_type).errors.is_empty() {
+            return vec![diagnostic(source_code, DiagnosticSeverity::Error, code, message.to_string(), first_offset)];
+        }
+    }
+
+    errors
+        .iter()
+        .take(MAX_SYNTAX_ERRORS)
+        .map(|error| {
+            diagnostic(
+                source_code,
+                DiagnosticSeverity::Error,
+                "syntax",
+                error.message.to_string(),
+                error_offset(error),
+            )
+        })
+        .collect()
+}
+
+fn error_offset(error: &oxc_diagnostics::OxcDiagnostic) -> Option<usize> {
+    error
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.first())
+        .map(|label| label.offset())
+}
+
+/// The source must end by returning the component it defines
+fn component_diagnostics(source_code: &str, program: &Program) -> Option<SourceDiagnostic> {
+    let missing = |offset| {
+        Some(diagnostic(
+            source_code,
+            DiagnosticSeverity::Error,
+            "missing_component",
+            "The source must end by returning its component, e.g. `return MyApp;`".to_string(),
+            offset,
+        ))
+    };
+
+    let Some(Statement::ReturnStatement(ret)) = program.body.last() else {
+        return missing(Some(source_code.trim_end().len()));
+    };
+    let offset = Some(ret.span.start as usize);
+    match ret.argument.as_ref().map(|argument| argument.without_parentheses()) {
+        Some(Expression::FunctionExpression(_) | Expression::ArrowFunctionExpression(_)) => None,
+        Some(Expression::Identifier(ident)) if defines_function(program, &ident.name) => None,
+        Some(Expression::Identifier(ident)) => Some(diagnostic(
+            source_code,
+            DiagnosticSeverity::Error,
+            "missing_component",
+            format!("`{}` is returned but isn't a function defined in the source", ident.name),
+            offset,
+        )),
+        _ => missing(offset),
+    }
+}
+
+/// Whether a top-level function declaration or variable holding a function is called `name`
+fn defines_function(program: &Program, name: &str) -> bool {
+    program.body.iter().any(|statement| match statement {
+        Statement::FunctionDeclaration(function) => function.id.as_ref().is_some_and(|id| id.name == name),
+        Statement::VariableDeclaration(declaration) => declaration.declarations.iter().any(|declarator| {
+            declarator.id.get_identifier_name().is_some_and(|id| id == name)
+                && matches!(
+                    declarator.init.as_ref().map(|init| init.without_parentheses()),
+                    Some(Expression::FunctionExpression(_) | Expression::ArrowFunctionExpression(_))
+                )
+        }),
+        _ => false,
+    })
+}
+
+/// Finds constructs apps may not use: running strings as code, reading cookies and talking
+/// to the network behind the host API's back
+struct ConstructChecker<'s> {
+    source_code: &'s str,
+    diagnostics: Vec<SourceDiagnostic>,
+}
+
+impl ConstructChecker<'_> {
+    fn report(&mut self, severity: DiagnosticSeverity, code: &str, message: String, offset: u32) {
+        self.diagnostics
+            .push(diagnostic(self.source_code, severity, code, message, Some(offset as usize)));
+    }
+}
+
+impl<'a> Visit<'a> for ConstructChecker<'_> {
+    fn visit_call_expression(&mut self, it: &CallExpression<'a>) {
+        let callee = &it.callee;
+        if is_global(callee, "eval") {
+            self.report(
+                DiagnosticSeverity::Error,
+                "eval",
+                "`eval` runs strings as code and isn't allowed".to_string(),
+                it.span.start,
+            );
+        } else if is_global(callee, "Function") {
+            self.report(
+                DiagnosticSeverity::Error,
+                "eval",
+                "`Function` runs strings as code and isn't allowed".to_string(),
+                it.span.start,
+            );
+        } else if is_global(callee, "fetch") {
+            self.check_fetch(it);
+        }
+        walk::walk_call_expression(self, it);
+    }
+
+    fn visit_new_expression(&mut self, it: &NewExpression<'a>) {
+        if is_global(&it.callee, "Function") {
+            self.report(
+                DiagnosticSeverity::Error,
+                "eval",
+                "`new Function` runs strings as code and isn't allowed".to_string(),
+                it.span.start,
+            );
+        } else if is_global(&it.callee, "XMLHttpRequest") {
+            self.report(
+                DiagnosticSeverity::Error,
+                "raw_network",
+                "`XMLHttpRequest` isn't allowed; store data with hostAPI.db".to_string(),
+                it.span.start,
+            );
+        }
+        walk::walk_new_expression(self, it);
+    }
+
+    fn visit_static_member_expression(&mut self, it: &StaticMemberExpression<'a>) {
+        if it.property.name == "cookie" && is_global(&it.object, "document") {
+            self.report(
+                DiagnosticSeverity::Error,
+                "document_cookie",
+                "`document.cookie` isn't allowed; store data with hostAPI.db".to_string(),
+                it.span.start,
+            );
+        }
+        walk::walk_static_member_expression(self, it);
+    }
+}
+
+impl ConstructChecker<'_> {
+    /// `fetch` may only call a fixed external URL, whose host becomes a network capability the
+    /// user consents to
+    fn check_fetch(&mut self, call: &CallExpression) {
+        let url = call.arguments.first().and_then(|argument| match argument {
+            Argument::StringLiteral(literal) => Some(literal.value.to_string()),
+            Argument::TemplateLiteral(template) => template.single_quasi().map(|quasi| quasi.to_string()),
+            _ => None,
+        });
+        let Some(url) = url else {
+            self.report(
+                DiagnosticSeverity::Error,
+                "raw_fetch",
+                "`fetch` with a URL built at runtime can reach any host; use a fixed https:// URL".to_string(),
+                call.span.start,
+            );
+            return;
+        };
+
+        let host = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .and_then(|rest| rest.split(['/', '?', '#']).next())
+            .filter(|host| !host.is_empty());
+        match host {
+            Some(host) => self.report(
+                DiagnosticSeverity::Warning,
+                "network",
+                format!("Connects to {}, which the user is asked to allow", host.to_lowercase()),
+                call.span.start,
+            ),
+            None => self.report(
+                DiagnosticSeverity::Error,
+                "raw_fetch",
+                "`fetch` to this server bypasses the app's permissions; use hostAPI.db".to_string(),
+                call.span.start,
+            ),
+        }
+    }
+}
+
+/// Whether `expression` is the global `name`, directly or through `window` or `globalThis`
+fn is_global(expression: &Expression, name: &str) -> bool {
+    expression.is_specific_id(name)
+        || ["window", "globalThis", "self"]
+            .iter()
+            .any(|global| expression.is_specific_member_access(global, name))
+}
+
+fn diagnostic(
+    source_code: &str,
+    severity: DiagnosticSeverity,
+    code: &str,
+    message: String,
+    offset: Option<usize>,
+) -> SourceDiagnostic {
+    let position = offset.map(|offset| line_column(source_code, offset));
+    SourceDiagnostic {
+        severity,
+        code: code.to_string(),
+        message,
+        line: position.map(|(line, _)| line),
+        column: position.map(|(_, column)| column),
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(source_code: &str, offset: usize) -> (usize, usize) {
+    let before = &source_code[..source_code.floor_char_boundary(offset.min(source_code.len()))];
+    let line_start = before.rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(source_code: &str) -> Vec<String> {
+        validate_source(source_code)
+            .diagnostics
+            .into_iter()
+            .map(|d| d.code)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_a_returned_component() {
+        let validation = validate_source(
+            "function MyApp(props) { return React.createElement('div', null, 'Hi'); }\nreturn MyApp;",
+        );
+        assert!(validation.valid);
+        assert!(validation.diagnostics.is_empty());
+
+        assert!(validate_source("return () => null;").valid);
+        assert!(validate_source("const MyApp = function () { return null; };\nreturn (MyApp);").valid);
+    }
+
+    #[test]
+    fn reports_markdown_fences_by_line() {
+        let validation = validate_source("```javascript\nfunction MyApp() {}\nreturn MyApp;\n```");
+        assert!(!validation.valid);
+        let lines: Vec<Option<usize>> = validation.diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![Some(1), Some(4)]);
+        assert!(validation.diagnostics.iter().all(|d| d.code == "markdown_fence"));
+    }
+
+    #[test]
+    fn names_typescript_and_jsx() {
+        assert_eq!(
+            codes("function MyApp(props: { name: string }) { return null; }\nreturn MyApp;"),
+            vec!["typescript"]
+        );
+        assert_eq!(
+            codes("function MyApp() { return <div>Hi</div>; }\nreturn MyApp;"),
+            vec!["jsx"]
+        );
+    }
+
+    #[test]
+    fn reports_other_syntax_errors_with_positions() {
+        let validation = validate_source("function MyApp() {\n  return 1 +;\n}\nreturn MyApp;");
+        assert!(!validation.valid);
+        assert_eq!(validation.diagnostics[0].code, "syntax");
+        assert_eq!(validation.diagnostics[0].line, Some(2));
+    }
+
+    #[test]
+    fn requires_returning_the_component() {
+        assert_eq!(codes("function MyApp() { return null; }"), vec!["missing_component"]);
+        assert_eq!(codes("function MyApp() { return null; }\nreturn 42;"), vec!["missing_component"]);
+        assert_eq!(codes("const x = 1;\nreturn Other;"), vec!["missing_component"]);
+    }
+
+    #[test]
+    fn rejects_running_strings_as_code() {
+        let component = "function MyApp() { return null; }\nreturn MyApp;";
+        for construct in ["eval('1')", "window.eval('1')", "Function('return 1')", "new Function('return 1')"] {
+            let source = format!("{}\n{}", construct, component);
+            assert_eq!(codes(&source), vec!["eval"], "{}", construct);
+        }
+    }
+
+    #[test]
+    fn checks_fetch_urls() {
+        let component = "function MyApp() { return null; }\nreturn MyApp;";
+
+        let validation = validate_source(&format!("fetch('https://API.example.com/data');\n{}", component));
+        assert!(validation.valid);
+        assert_eq!(validation.diagnostics[0].code, "network");
+        assert_eq!(validation.diagnostics[0].severity, DiagnosticSeverity::Warning);
+        assert!(validation.diagnostics[0].message.contains("api.example.com"));
+
+        assert_eq!(codes(&format!("fetch('/api/db/apps');\n{}", component)), vec!["raw_fetch"]);
+        assert_eq!(codes(&format!("fetch(url);\n{}", component)), vec!["raw_fetch"]);
+        assert_eq!(codes(&format!("new XMLHttpRequest();\n{}", component)), vec!["raw_network"]);
+        assert_eq!(codes(&format!("document.cookie;\n{}", component)), vec!["document_cookie"]);
+    }
+}
@@ -0,0 +1,344 @@
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::database::Database;
+use crate::listing::document_from_row;
+use crate::models::{project_branches, Document, DEFAULT_BRANCH};
+use crate::sources::{save_source, split_source};
+
+impl Database {
+    /// Atomically store `fields` as the next version on `branch` of a project.
+    ///
+    /// Reading the project's version counter, inserting the version and advancing the
+    /// counter and branch head happen in one write transaction, so concurrent saves are
+    /// serialized. Returns `None` if the project or branch doesn't exist.
+    pub async fn append_project_version(
+        &self,
+        project_id: &str,
+        branch: &str,
+        fields: Value,
+    ) -> Result<Option<Document>, sqlx::Error> {
+        Ok(self
+            .append_project_versions(project_id, branch, vec![fields])
+            .await?
+            .and_then(|mut documents| documents.pop()))
+    }
+
+    /// Store each of `versions` in turn as the next version on `branch` of a project, all in
+    /// one write transaction, so either every version is saved or none is. Returns `None` if
+    /// the project or branch doesn't exist.
+    pub async fn append_project_versions(
+        &self,
+        project_id: &str,
+        branch: &str,
+        versions: Vec<Value>,
+    ) -> Result<Option<Vec<Document>>, sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        // Writing first takes SQLite's write lock before anything is read, so a concurrent
+        // save waits here instead of reading the same version counter
+        let touched = sqlx::query(
+            r#"
+            UPDATE documents
+            SET updated_at = ?
+            WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+            "#,
+        )
+        .bind(now.to_rfc3339())
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if touched == 0 {
+            return Ok(None);
+        }
+
+        let project_row = sqlx::query(
+            r#"
+            SELECT id, data
+            FROM documents
+            WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+            "#,
+        )
+        .bind(project_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let project_doc_id: String = project_row.get("id");
+        let project_data: Value = serde_json::from_str(project_row.get("data")).unwrap();
+
+        let mut branches = project_branches(&project_data);
+        let mut parent_version = match branches.get(branch) {
+            Some(head) => *head,
+            None => return Ok(None),
+        };
+
+        // Numbered after the highest version actually stored, so a stale `current_version`
+        // written back by another request can't make saves collide with existing versions
+        let highest_version: i64 = sqlx::query(
+            r#"
+            SELECT COALESCE(MAX(json_extract(data, '$.version_number')), 0) AS highest_version
+            FROM documents
+            WHERE collection = 'project_versions' AND json_extract(data, '$.project_id') = ?
+            "#,
+        )
+        .bind(project_id)
+        .fetch_one(&mut *tx)
+        .await?
+        .get("highest_version");
+        // Pruned versions keep their numbers taken
+        let mut current_version = project_data
+            .get("current_version")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0)
+            .max(highest_version) as i32;
+
+        let mut documents = Vec::with_capacity(versions.len());
+        for mut fields in versions {
+            let next_version = current_version + 1;
+            let document_id = Uuid::new_v4().to_string();
+
+            if let Some(data_obj) = fields.as_object_mut() {
+                data_obj.insert("id".to_string(), Value::String(Uuid::new_v4().to_string()));
+                data_obj.insert("project_id".to_string(), Value::String(project_id.to_string()));
+                data_obj.insert("version_number".to_string(), Value::Number(next_version.into()));
+                data_obj.insert("branch".to_string(), Value::String(branch.to_string()));
+                if parent_version > 0 {
+                    data_obj.insert("parent_version".to_string(), Value::Number(parent_version.into()));
+                }
+                data_obj.insert("created_at".to_string(), Value::String(now.to_rfc3339()));
+            }
+
+            let (stored, source) = split_source("project_versions", &mut fields);
+            if let Some((hash, source_code)) = source {
+                save_source(&mut tx, &hash, &source_code).await?;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO documents (id, collection, data, created_at, updated_at)
+                VALUES (?, 'project_versions', ?, ?, ?)
+                "#,
+            )
+            .bind(&document_id)
+            .bind(serde_json::to_string(&stored).unwrap())
+            .bind(now.to_rfc3339())
+            .bind(now.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+            documents.push(Document {
+                id: document_id,
+                collection: "project_versions".to_string(),
+                data: fields,
+                created_at: now,
+                updated_at: now,
+            });
+            current_version = next_version;
+            parent_version = next_version;
+        }
+
+        // Update project's current_version and the branch head, leaving its other fields alone
+        branches.insert(branch.to_string(), parent_version);
+        sqlx::query(
+            r#"
+            UPDATE documents
+            SET data = json_set(data, '$.current_version', ?, '$.branches', json(?), '$.updated_at', ?),
+                updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(current_version)
+        .bind(serde_json::to_string(&branches).unwrap())
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .bind(&project_doc_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(documents))
+    }
+
+    /// Add a branch with its head at `head_version` to a project, writing only that branch
+    /// so heads moved by concurrent saves are kept. Returns `false` if the project doesn't
+    /// exist or already has a branch with that name.
+    pub async fn add_project_branch(
+        &self,
+        project_id: &str,
+        name: &str,
+        head_version: i32,
+    ) -> Result<bool, sqlx::Error> {
+        let now = Utc::now().to_rfc3339();
+        // Branch names are letters, digits, `-`, `_` and `.`, so quoting them is enough
+        let branch_path = format!("$.branches.\"{}\"", name);
+        let affected_rows = sqlx::query(
+            r#"
+            UPDATE documents
+            SET data = json_set(
+                    data,
+                    '$.branches',
+                    json_set(
+                        -- Projects from before branching only have `main`
+                        COALESCE(
+                            json_extract(data, '$.branches'),
+                            json_object(?, COALESCE(json_extract(data, '$.current_version'), 0))
+                        ),
+                        ?,
+                        ?
+                    ),
+                    '$.updated_at',
+                    ?
+                ),
+                updated_at = ?
+            WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+              AND json_extract(data, ?) IS NULL
+            "#,
+        )
+        .bind(DEFAULT_BRANCH)
+        .bind(format!("$.\"{}\"", name))
+        .bind(head_version)
+        .bind(&now)
+        .bind(&now)
+        .bind(project_id)
+        .bind(&branch_path)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(affected_rows > 0)
+    }
+
+    /// Delete a project together with all of its versions in one transaction. Returns `false`
+    /// if the project doesn't exist.
+    pub async fn delete_project(&self, project_id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let affected_rows = sqlx::query(
+            r#"
+            DELETE FROM documents
+            WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+            "#,
+        )
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if affected_rows == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM documents
+            WHERE collection = 'project_versions' AND json_extract(data, '$.project_id') = ?
+            "#,
+        )
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Give every version that shares its number with an older version of the same project
+    /// the project's next free number, returning how many were renumbered.
+    ///
+    /// Such duplicates come from concurrent saves before version numbering was transactional.
+    pub(crate) async fn renumber_duplicate_versions(&self) -> Result<usize, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let duplicates = sqlx::query(
+            r#"
+            SELECT d.id, json_extract(d.data, '$.project_id') AS project_id
+            FROM documents d
+            WHERE d.collection = 'project_versions'
+              AND EXISTS (
+                SELECT 1 FROM documents o
+                WHERE o.collection = 'project_versions'
+                  AND json_extract(o.data, '$.project_id') = json_extract(d.data, '$.project_id')
+                  AND json_extract(o.data, '$.version_number') = json_extract(d.data, '$.version_number')
+                  AND (o.created_at < d.created_at OR (o.created_at = d.created_at AND o.id < d.id))
+              )
+            ORDER BY d.created_at, d.id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for row in &duplicates {
+            let project_id: String = row.get("project_id");
+            let next_version: i64 = sqlx::query(
+                r#"
+                SELECT MAX(
+                    COALESCE((
+                        SELECT MAX(json_extract(data, '$.version_number')) FROM documents
+                        WHERE collection = 'project_versions' AND json_extract(data, '$.project_id') = ?
+                    ), 0),
+                    COALESCE((
+                        SELECT MAX(json_extract(data, '$.current_version')) FROM documents
+                        WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+                    ), 0)
+                ) + 1 AS next_version
+                "#,
+            )
+            .bind(&project_id)
+            .bind(&project_id)
+            .fetch_one(&mut *tx)
+            .await?
+            .get("next_version");
+
+            sqlx::query("UPDATE documents SET data = json_set(data, '$.version_number', ?) WHERE id = ?")
+                .bind(next_version)
+                .bind(row.get::<String, _>("id"))
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                r#"
+                UPDATE documents SET data = json_set(data, '$.current_version', ?)
+                WHERE collection = 'projects' AND json_extract(data, '$.id') = ?
+                "#,
+            )
+            .bind(next_version)
+            .bind(&project_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        if !duplicates.is_empty() {
+            tracing::warn!("Renumbered {} project versions that shared a version number", duplicates.len());
+        }
+        Ok(duplicates.len())
+    }
+
+    /// A project's version by its number
+    pub async fn get_project_version(
+        &self,
+        project_id: &str,
+        version_number: i32,
+    ) -> Result<Option<Document>, sqlx::Error> {
+        // Matches the expressions of the unique version number index, so this is a lookup
+        let row = sqlx::query(
+            r#"
+            SELECT id, collection, data, created_at, updated_at
+            FROM documents
+            WHERE collection = 'project_versions'
+              AND json_extract(data, '$.project_id') = ?
+              AND json_extract(data, '$.version_number') = ?
+            "#,
+        )
+        .bind(project_id)
+        .bind(version_number)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let mut documents: Vec<Document> = row.iter().map(document_from_row).collect();
+        self.hydrate_sources(&mut documents).await?;
+        Ok(documents.pop())
+    }
+}
@@ -1,3 +1,6 @@
+// name: DB Viewer
+// description: Browse and manage your database collections and documents.
+// icon: 🗃️
 function DBViewer({ React, app, hostAPI }) {
   const [collections, setCollections] = React.useState([]);
   const [selectedCollection, setSelectedCollection] = React.useState("");
@@ -1,3 +1,6 @@
+// name: Notepad
+// description: A simple notepad for quick notes and ideas.
+// icon: 📝
 function Notepad({ React, app, hostAPI }) {
   const [note, setNote] = React.useState("");
   const [loading, setLoading] = React.useState(true);